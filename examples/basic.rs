@@ -43,9 +43,12 @@ fn main() {
 /// Increments a value that lives in shared memory
 fn increment_value(shmem_flink: &str, thread_num: usize, max: u8) {
     // Create or open the shared memory mapping
-    let shmem = match ShmemConf::new().size(4096).flink(shmem_flink).create() {
-        Ok(m) => m,
-        Err(ShmemError::LinkExists) => ShmemConf::new().flink(shmem_flink).open().unwrap(),
+    let shmem = match ShmemConf::new()
+        .size(4096)
+        .flink(shmem_flink)
+        .open_or_create()
+    {
+        Ok((m, _)) => m,
         Err(e) => {
             eprintln!("Unable to create or open shmem flink {shmem_flink} : {e}");
             return;
@@ -3,13 +3,12 @@ use shared_memory::*;
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Attempt to create a mapping or open if it already exists
     println!("Getting the shared memory mapping");
-    let shmem = match ShmemConf::new().size(4096).flink("event_mapping").create() {
-        Ok(m) => m,
-        Err(ShmemError::LinkExists) => ShmemConf::new().flink("event_mapping").open()?,
-        Err(e) => return Err(Box::new(e)),
-    };
+    let (shmem, origin) = ShmemConf::new()
+        .size(4096)
+        .flink("event_mapping")
+        .open_or_create()?;
 
-    if shmem.is_owner() {
+    if origin == ShmemOrigin::Created {
         //Create an event in the shared memory
         println!("Creating event in shared memory");
         let (evt, used_bytes) = unsafe { Event::new(shmem.as_ptr(), true)? };
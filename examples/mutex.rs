@@ -42,9 +42,12 @@ fn main() {
 
 fn increment_value(shmem_flink: &str, thread_num: usize) {
     // Create or open the shared memory mapping
-    let shmem = match ShmemConf::new().size(4096).flink(shmem_flink).create() {
-        Ok(m) => m,
-        Err(ShmemError::LinkExists) => ShmemConf::new().flink(shmem_flink).open().unwrap(),
+    let (shmem, origin) = match ShmemConf::new()
+        .size(4096)
+        .flink(shmem_flink)
+        .open_or_create()
+    {
+        Ok(v) => v,
         Err(e) => {
             eprintln!("Unable to create or open shmem flink {shmem_flink} : {e}");
             return;
@@ -60,7 +63,7 @@ fn increment_value(shmem_flink: &str, thread_num: usize) {
     };
 
     // Initialize or wait for initialized mutex
-    let mutex = if shmem.is_owner() {
+    let mutex = if origin == ShmemOrigin::Created {
         is_init.store(0, Ordering::Relaxed);
         // Initialize the mutex
         let (lock, _bytes_used) = unsafe {
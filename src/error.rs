@@ -5,6 +5,10 @@ pub enum ShmemError {
     MapSizeZero,
     NoLinkOrOsId,
     FlinkInvalidOsId,
+    FlinkVersionMismatch(u32),
+    FlinkStale,
+    FlinkOwnerMismatch(u32),
+    FlinkBackendMismatch(String),
     LinkCreateFailed(std::io::Error),
     LinkWriteFailed(std::io::Error),
     LinkExists,
@@ -14,6 +18,29 @@ pub enum ShmemError {
     MappingIdExists,
     MapCreateFailed(u32),
     MapOpenFailed(u32),
+    MapResizeFailed(u32),
+    MapFlushFailed(u32),
+    MapPermissionsFailed(u32),
+    HugePagesUnavailable(u32),
+    RangeOutOfBounds { offset: usize, len: usize },
+    RangeNotAligned { offset: usize, len: usize },
+    AddressInUse(usize),
+    MapLockFailed(u32),
+    MemlockLimitExceeded { len: usize, limit: u64 },
+    NumaPolicyFailed(u32),
+    NumaQueryFailed(u32),
+    MapProtectFailed(u32),
+    MapAdviseFailed(u32),
+    MapSealFailed(u32),
+    NotSealed,
+    HeaderInvalid,
+    HeaderVersionMismatch(u32),
+    HeaderSizeMismatch { expected: usize, found: usize },
+    SchemaMismatch { id: u64, version: u32 },
+    HeaderNotSupported,
+    ListFailed(std::io::Error),
+    MapStatFailed(u32),
+    WaitTimedOut,
     UnknownOsError(u32),
     Unknown(String),
 }
@@ -24,6 +51,10 @@ impl std::fmt::Display for ShmemError {
             ShmemError::MapSizeZero => f.write_str("You cannot create a shared memory mapping of 0 size"),
             ShmemError::NoLinkOrOsId => f.write_str("Tried to open mapping without flink path or os_id"),
             ShmemError::FlinkInvalidOsId => f.write_str("Tried to open mapping from both flink and os_id but the flink did not point to the same os_id"),
+            ShmemError::FlinkVersionMismatch(version) => write!(f, "The flink has unsupported format version {version}"),
+            ShmemError::FlinkStale => f.write_str("The flink points to shared memory that was recreated since the flink was written"),
+            ShmemError::FlinkOwnerMismatch(uid) => write!(f, "The flink is owned by an unexpected user {uid}"),
+            ShmemError::FlinkBackendMismatch(backend) => write!(f, "The flink points to shared memory of another backend '{backend}'"),
             ShmemError::LinkCreateFailed(err) => write!(f, "Creating the link file failed, {err}"),
             ShmemError::LinkWriteFailed(err) => write!(f, "Writing the link file failed, {err}"),
             ShmemError::LinkExists => f.write_str("Shared memory link already exists"),
@@ -33,6 +64,29 @@ impl std::fmt::Display for ShmemError {
             ShmemError::MappingIdExists => f.write_str("Shared memory OS specific ID already exists"),
             ShmemError::MapCreateFailed(err) => write!(f, "Creating the shared memory failed, os error {err}"),
             ShmemError::MapOpenFailed(err) => write!(f, "Opening the shared memory failed, os error {err}"),
+            ShmemError::MapResizeFailed(err) => write!(f, "Resizing the shared memory failed, os error {err}"),
+            ShmemError::MapFlushFailed(err) => write!(f, "Flushing the shared memory failed, os error {err}"),
+            ShmemError::MapPermissionsFailed(err) => write!(f, "Setting the shared memory permissions failed, os error {err}"),
+            ShmemError::HugePagesUnavailable(err) => write!(f, "Huge pages are not available for this mapping, os error {err}"),
+            ShmemError::RangeOutOfBounds { offset, len } => write!(f, "Range of {len} bytes at offset {offset} is outside of the mapping"),
+            ShmemError::RangeNotAligned { offset, len } => write!(f, "Range of {len} bytes at offset {offset} does not start on a page boundary"),
+            ShmemError::AddressInUse(addr) => write!(f, "The address {addr:#x} requested for the mapping is already in use"),
+            ShmemError::MapLockFailed(err) => write!(f, "Locking the shared memory failed, os error {err}"),
+            ShmemError::MemlockLimitExceeded { len, limit } => write!(f, "Locking {len} bytes of shared memory exceeds the RLIMIT_MEMLOCK limit of {limit} bytes"),
+            ShmemError::NumaPolicyFailed(err) => write!(f, "Setting the NUMA policy of the shared memory failed, os error {err}"),
+            ShmemError::NumaQueryFailed(err) => write!(f, "Querying the NUMA placement of the shared memory failed, os error {err}"),
+            ShmemError::MapProtectFailed(err) => write!(f, "Changing the protection of the shared memory failed, os error {err}"),
+            ShmemError::MapAdviseFailed(err) => write!(f, "Advising the kernel about the shared memory failed, os error {err}"),
+            ShmemError::MapSealFailed(err) => write!(f, "Sealing the shared memory failed, os error {err}"),
+            ShmemError::NotSealed => f.write_str("The shared memory does not have the required seals"),
+            ShmemError::HeaderInvalid => f.write_str("The shared memory does not start with a valid header"),
+            ShmemError::HeaderVersionMismatch(version) => write!(f, "The shared memory header has unsupported format version {version}"),
+            ShmemError::HeaderSizeMismatch { expected, found } => write!(f, "The shared memory header describes a payload of {expected} bytes but only {found} bytes are mapped"),
+            ShmemError::SchemaMismatch { id, version } => write!(f, "The shared memory follows schema {id} version {version}"),
+            ShmemError::HeaderNotSupported => f.write_str("The shared memory header cannot be used with ring buffers or windows"),
+            ShmemError::ListFailed(err) => write!(f, "Listing the shared memory failed, {err}"),
+            ShmemError::MapStatFailed(err) => write!(f, "Querying the status of the shared memory failed, os error {err}"),
+            ShmemError::WaitTimedOut => f.write_str("Timed out waiting for the shared memory to be created"),
             ShmemError::UnknownOsError(err) => write!(f, "An unexpected OS error occurred, os error {err}"),
             ShmemError::Unknown(err) => write!(f, "{err}"),
         }
@@ -46,6 +100,7 @@ impl std::error::Error for ShmemError {
             ShmemError::LinkWriteFailed(err) => Some(err),
             ShmemError::LinkOpenFailed(err) => Some(err),
             ShmemError::LinkReadFailed(err) => Some(err),
+            ShmemError::ListFailed(err) => Some(err),
             _ => None,
         }
     }
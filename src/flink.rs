@@ -0,0 +1,265 @@
+use std::ffi::OsString;
+use std::fs::{hard_link, remove_file, rename, File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::{debug, os_impl, trace, Result, Shmem, ShmemError};
+
+/// Suffix of the next temporary flink of this process
+static NEXT_TMP: AtomicUsize = AtomicUsize::new(0);
+
+/// First line of the flinks written by this crate, followed by the format version
+const FLINK_MAGIC: &str = "shmem-flink";
+/// Version of the flink format
+const FLINK_VERSION: u32 = 1;
+
+/// Content of a flink
+///
+/// Legacy flinks only hold the os_id, the other fields are then unknown.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub(crate) struct Flink {
+    pub os_id: String,
+    pub size: Option<usize>,
+    pub creator_pid: Option<u32>,
+    /// Nonce telling apart mappings created under the same os_id, see `generation()`
+    pub generation: Option<u64>,
+    /// Name of the backend from `ShmemConfExt::backend_name()`
+    pub backend: Option<String>,
+}
+
+impl Flink {
+    /// Describes a mapping this process just created
+    pub fn new(
+        os_id: &str,
+        size: usize,
+        generation: Option<u64>,
+        ext: &os_impl::ShmemConfExt,
+    ) -> Self {
+        Self {
+            os_id: os_id.to_string(),
+            size: Some(size),
+            creator_pid: Some(std::process::id()),
+            generation,
+            backend: Some(ext.backend_name().to_string()),
+        }
+    }
+
+    /// Returns the content of the flink file
+    pub fn serialize(&self) -> String {
+        let mut content = format!("{FLINK_MAGIC} {FLINK_VERSION}\nos_id={}\n", self.os_id);
+        if let Some(size) = self.size {
+            content += &format!("size={size}\n");
+        }
+        if let Some(pid) = self.creator_pid {
+            content += &format!("pid={pid}\n");
+        }
+        if let Some(generation) = self.generation {
+            content += &format!("generation={generation:x}\n");
+        }
+        if let Some(ref backend) = self.backend {
+            content += &format!("backend={backend}\n");
+        }
+        content
+    }
+
+    /// Parses the content of a flink file
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut lines = content.lines();
+        let version = match lines.next().and_then(|l| l.strip_prefix(FLINK_MAGIC)) {
+            Some(version) => version,
+            // Legacy flinks hold the raw os_id
+            None => {
+                return Ok(Self {
+                    os_id: content.to_string(),
+                    ..Default::default()
+                })
+            }
+        };
+        match version.trim().parse::<u32>() {
+            Ok(FLINK_VERSION) => {}
+            Ok(v) => return Err(ShmemError::FlinkVersionMismatch(v)),
+            Err(_) => return Err(invalid("bad version")),
+        }
+        // The last line is only complete once the creator wrote the newline
+        if !content.ends_with('\n') {
+            return Err(invalid("truncated"));
+        }
+
+        let mut flink = Self::default();
+        for line in lines {
+            let (key, value) = line.split_once('=').ok_or_else(|| invalid(line))?;
+            match key {
+                "os_id" => flink.os_id = value.to_string(),
+                "size" => flink.size = Some(value.parse().map_err(|_| invalid(line))?),
+                "pid" => flink.creator_pid = Some(value.parse().map_err(|_| invalid(line))?),
+                "generation" => {
+                    flink.generation =
+                        Some(u64::from_str_radix(value, 16).map_err(|_| invalid(line))?)
+                }
+                "backend" => flink.backend = Some(value.to_string()),
+                // Added by newer versions of this format
+                _ => {}
+            }
+        }
+        if flink.os_id.is_empty() {
+            return Err(invalid("missing os_id"));
+        }
+        Ok(flink)
+    }
+}
+
+impl Shmem {
+    /// Checks that the mapping is the one the flink was written for
+    pub(crate) fn validate_flink(&self, flink: &Flink) -> Result<()> {
+        match (flink.generation, generation(&self.mapping, self.header())) {
+            (Some(expected), Some(found)) if expected != found => Err(ShmemError::FlinkStale),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the nonce recorded in the flink of a mapping
+///
+/// This is the identity of the backing object when there is one, so mappings without a header are told apart too,
+/// and the generation of the header otherwise.
+pub(crate) fn generation(
+    mapping: &os_impl::MapData,
+    header: Option<crate::ShmemHeader>,
+) -> Option<u64> {
+    mapping.object_id().or(header.map(|h| h.generation))
+}
+
+/// Atomically creates the flink at `path`
+///
+/// The content is written to a temporary file next to it which is then renamed, or hard linked when an existing
+/// flink must not be replaced. Readers never see a partially written flink.
+pub(crate) fn write(
+    path: &Path,
+    flink: &Flink,
+    overwrite: bool,
+    ext: &os_impl::ShmemConfExt,
+) -> Result<()> {
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| ShmemError::LinkCreateFailed(ErrorKind::InvalidInput.into()))?;
+    let mut tmp_name = OsString::from(".");
+    tmp_name.push(file_name);
+    tmp_name.push(format!(
+        ".{:X}_{:X}.tmp",
+        std::process::id(),
+        NEXT_TMP.fetch_add(1, Ordering::Relaxed)
+    ));
+    let tmp_path = path.with_file_name(tmp_name);
+
+    let mut open_options = OpenOptions::new();
+    open_options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        open_options
+            .mode(ext.flink_mode())
+            .custom_flags(libc::O_NOFOLLOW);
+    }
+    let mut f = open_options
+        .open(&tmp_path)
+        .map_err(ShmemError::LinkCreateFailed)?;
+
+    let res = (|| {
+        #[cfg(unix)]
+        ext.apply_flink_permissions(&f)
+            .map_err(ShmemError::LinkCreateFailed)?;
+        #[cfg(not(unix))]
+        let _ = ext;
+        f.write_all(flink.serialize().as_bytes())
+            .map_err(ShmemError::LinkWriteFailed)?;
+        if overwrite {
+            rename(&tmp_path, path).map_err(ShmemError::LinkCreateFailed)
+        } else {
+            match hard_link(&tmp_path, path) {
+                Ok(_) => Ok(()),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => Err(ShmemError::LinkExists),
+                Err(e) => Err(ShmemError::LinkCreateFailed(e)),
+            }
+        }
+    })();
+    trace!("Writing flink through {:?} == {:?}", tmp_path, res);
+    // Already gone if it was renamed
+    let _ = remove_file(&tmp_path);
+    res
+}
+
+/// Reads the flink at `path`
+///
+/// Symbolic links are not followed on unix. Anything but a regular file is refused, opening does not block on a fifo.
+pub(crate) fn read(path: &Path, ext: &os_impl::ShmemConfExt) -> Result<Flink> {
+    let mut f = open(path, ext)?;
+    let mut content = String::new();
+    f.read_to_string(&mut content)
+        .map_err(ShmemError::LinkReadFailed)?;
+    Flink::parse(&content)
+}
+
+/// Deletes the flink at `path` unless it points to another mapping than `os_id`
+///
+/// On unix, the file that was read is compared with the one at `path` right before deleting it, a flink republished
+/// in the meantime is kept.
+pub(crate) fn remove(path: &Path, os_id: Option<&str>, ext: &os_impl::ShmemConfExt) {
+    let mut f = match open(path, ext) {
+        Ok(v) => v,
+        Err(_) => return,
+    };
+    let mut content = String::new();
+    if let (Some(os_id), Ok(_)) = (os_id, f.read_to_string(&mut content)) {
+        if Flink::parse(&content).is_ok_and(|flink| flink.os_id != os_id) {
+            return;
+        }
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        match (f.metadata(), std::fs::symlink_metadata(path)) {
+            (Ok(opened), Ok(cur)) if opened.dev() == cur.dev() && opened.ino() == cur.ino() => {}
+            _ => return,
+        }
+    }
+    debug!("Deleting file link {}", path.to_string_lossy());
+    let _ = remove_file(path);
+}
+
+/// Opens the flink at `path` for reading
+fn open(path: &Path, ext: &os_impl::ShmemConfExt) -> Result<File> {
+    let mut open_options = OpenOptions::new();
+    open_options.read(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        open_options.custom_flags(libc::O_NOFOLLOW | libc::O_NONBLOCK);
+    }
+    let f = open_options
+        .open(path)
+        .map_err(ShmemError::LinkOpenFailed)?;
+    match f.metadata() {
+        Ok(meta) if meta.is_file() => {}
+        Ok(_) => {
+            return Err(ShmemError::LinkOpenFailed(Error::new(
+                ErrorKind::InvalidInput,
+                "the flink is not a regular file",
+            )))
+        }
+        Err(e) => return Err(ShmemError::LinkOpenFailed(e)),
+    }
+    #[cfg(unix)]
+    ext.check_flink_owner(&f)?;
+    #[cfg(not(unix))]
+    let _ = ext;
+    Ok(f)
+}
+
+/// Error for flinks whose content cannot be used
+pub(crate) fn invalid(what: &str) -> ShmemError {
+    ShmemError::LinkReadFailed(Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid flink content : {what}"),
+    ))
+}
@@ -0,0 +1,284 @@
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::{debug, ReadOnlyShmem, Shmem, ShmemConf, ShmemError};
+
+/// Identifies shared memory that starts with a header
+const HEADER_MAGIC: u64 = u64::from_be_bytes(*b"shmemhdr");
+/// Version of the header layout
+const HEADER_FORMAT_VERSION: u32 = 1;
+/// Size of the header, the payload that follows it is aligned on 64 bytes
+pub(crate) const HEADER_LEN: usize = std::mem::size_of::<RawHeader>();
+
+/// Layout of the header at the start of the shared memory
+#[repr(C, align(64))]
+struct RawHeader {
+    magic: u64,
+    format_version: u32,
+    header_len: u32,
+    schema_id: u64,
+    schema_version: u32,
+    creator_pid: u32,
+    //Nanoseconds since the unix epoch
+    created_at: u64,
+    payload_len: u64,
+    initialized: AtomicU32,
+    //Handles attached with `CleanupPolicy::RefCount`
+    ref_count: AtomicU32,
+    //Random value telling apart mappings created under the same os_id
+    generation: u64,
+    //Address the creator chose with `fixed_address()` or `address_hint()`, 0 if none
+    base_addr: u64,
+    //Whether openers must map at `base_addr`
+    base_fixed: u32,
+}
+
+/// Schema the payload of the shared memory must follow
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct HeaderConf {
+    pub schema_id: u64,
+    pub schema_version: u32,
+}
+
+/// Content of the header of a shared memory
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ShmemHeader {
+    /// Version of the header layout
+    pub format_version: u32,
+    /// User defined identifier of the payload layout
+    pub schema_id: u64,
+    /// User defined version of the payload layout
+    pub schema_version: u32,
+    /// Pid of the process that created the shared memory
+    pub creator_pid: u32,
+    /// When the shared memory was created
+    pub created: SystemTime,
+    /// Size of the payload that follows the header
+    pub payload_len: usize,
+    /// Whether the creator marked the payload as initialized
+    pub initialized: bool,
+    /// Random value telling apart mappings created under the same os_id
+    pub generation: u64,
+    /// Address the creator mapped the shared memory at when it asked for one on unix
+    pub base_address: Option<usize>,
+}
+
+impl ShmemConf {
+    /// Starts the shared memory with a header describing its content
+    ///
+    /// `create()` writes a header that holds a magic number, the format version of the header, the given schema id and
+    /// version, the creator's pid, a creation timestamp and an initialized flag. `open()` validates it and fails with
+    /// `HeaderInvalid`, `HeaderVersionMismatch`, `HeaderSizeMismatch` or `SchemaMismatch` when the shared memory
+    /// does not match.
+    ///
+    /// `create()` and `open()` fail with `HeaderNotSupported` for ring buffers and windows on unix.
+    ///
+    /// `Shmem::as_ptr()` and `Shmem::len()` only cover the payload after the header, which is 128 bytes long. The payload
+    /// does not start on a page boundary, see `Shmem::protect()` for the offsets the page granular functions accept. Creators call
+    /// `Shmem::set_initialized()` once the payload is ready, which openers check with `Shmem::is_initialized()`.
+    pub fn header(mut self, schema_id: u64, schema_version: u32) -> Self {
+        self.header = Some(HeaderConf {
+            schema_id,
+            schema_version,
+        });
+        self
+    }
+}
+
+impl Shmem {
+    /// Returns the content of the header, `None` if the mapping was not configured with `ShmemConf::header()`
+    pub fn header(&self) -> Option<ShmemHeader> {
+        self.config.header?;
+        Some(unsafe { read(self.mapping.as_mut_ptr()) })
+    }
+
+    /// Returns whether the creator marked the payload as initialized
+    ///
+    /// Always true for mappings without a header.
+    pub fn is_initialized(&self) -> bool {
+        if self.config.header.is_none() {
+            return true;
+        }
+        unsafe { raw(self.mapping.as_mut_ptr()) }
+            .initialized
+            .load(Ordering::Acquire)
+            != 0
+    }
+
+    /// Marks the payload as initialized for the openers
+    ///
+    /// Writes made to the payload before this call are visible to openers that see `is_initialized()` return true.
+    /// Does nothing for mappings without a header.
+    pub fn set_initialized(&self) {
+        if self.config.header.is_none() {
+            return;
+        }
+        unsafe { raw(self.mapping.as_mut_ptr()) }
+            .initialized
+            .store(1, Ordering::Release);
+    }
+
+    /// Size of the header in front of the payload
+    pub(crate) fn header_len(&self) -> usize {
+        if self.config.header.is_some() {
+            HEADER_LEN
+        } else {
+            0
+        }
+    }
+
+    /// Moves the mapping to the address its creator recorded in the header
+    pub(crate) fn map_at_recorded_base(&mut self) -> Result<(), ShmemError> {
+        if self.config.header.is_none() {
+            return Ok(());
+        }
+        let raw = unsafe { raw(self.mapping.as_mut_ptr()) };
+        if raw.base_addr == 0 {
+            return Ok(());
+        }
+        let (base, fixed) = (raw.base_addr as usize, raw.base_fixed != 0);
+        self.mapping.relocate(base, fixed, &self.config.ext)
+    }
+
+    /// Updates the payload size stored in the header after a resize
+    #[cfg(unix)]
+    pub(crate) fn update_header_payload_len(&self) {
+        if self.config.header.is_some() {
+            unsafe {
+                (*(self.mapping.as_mut_ptr() as *mut RawHeader)).payload_len = self.len() as u64
+            };
+        }
+    }
+}
+
+impl ReadOnlyShmem {
+    /// Returns the content of the header
+    ///
+    /// See `Shmem::header()`
+    pub fn header(&self) -> Option<ShmemHeader> {
+        self.inner.header()
+    }
+
+    /// Returns whether the creator marked the payload as initialized
+    ///
+    /// See `Shmem::is_initialized()`
+    pub fn is_initialized(&self) -> bool {
+        self.inner.is_initialized()
+    }
+}
+
+unsafe fn raw<'a>(ptr: *const u8) -> &'a RawHeader {
+    &*(ptr as *const RawHeader)
+}
+
+/// Returns the reference count stored in the header at `ptr`
+pub(crate) unsafe fn ref_count<'a>(ptr: *const u8) -> &'a AtomicU32 {
+    &raw(ptr).ref_count
+}
+
+/// Returns a value that is unlikely to be picked by another mapping
+fn generation(created_at: u64) -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let mut hasher = DefaultHasher::new();
+    (
+        created_at,
+        std::process::id(),
+        COUNTER.fetch_add(1, Ordering::Relaxed),
+    )
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Writes a new header at `ptr`
+///
+/// `base` is the address recorded for openers and whether they must use it
+pub(crate) unsafe fn write(
+    ptr: *mut u8,
+    conf: HeaderConf,
+    payload_len: usize,
+    base: Option<(usize, bool)>,
+) {
+    let created_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64;
+    debug!(
+        "Writing header for schema {}:{}",
+        conf.schema_id, conf.schema_version
+    );
+    std::ptr::write(
+        ptr as *mut RawHeader,
+        RawHeader {
+            magic: HEADER_MAGIC,
+            format_version: HEADER_FORMAT_VERSION,
+            header_len: HEADER_LEN as u32,
+            schema_id: conf.schema_id,
+            schema_version: conf.schema_version,
+            creator_pid: std::process::id(),
+            created_at,
+            payload_len: payload_len as u64,
+            initialized: AtomicU32::new(0),
+            ref_count: AtomicU32::new(0),
+            generation: generation(created_at),
+            base_addr: base.map_or(0, |(addr, _)| addr as u64),
+            base_fixed: base.is_some_and(|(_, fixed)| fixed) as u32,
+        },
+    );
+}
+
+/// Reads the header at `ptr`
+pub(crate) unsafe fn read(ptr: *const u8) -> ShmemHeader {
+    let raw = raw(ptr);
+    ShmemHeader {
+        format_version: raw.format_version,
+        schema_id: raw.schema_id,
+        schema_version: raw.schema_version,
+        creator_pid: raw.creator_pid,
+        created: UNIX_EPOCH + Duration::from_nanos(raw.created_at),
+        payload_len: raw.payload_len as usize,
+        initialized: raw.initialized.load(Ordering::Acquire) != 0,
+        generation: raw.generation,
+        base_address: (raw.base_addr != 0).then_some(raw.base_addr as usize),
+    }
+}
+
+/// Reads the header at `ptr` if the `map_size` bytes there start with one
+pub(crate) unsafe fn read_checked(ptr: *const u8, map_size: usize) -> Option<ShmemHeader> {
+    if map_size < HEADER_LEN || raw(ptr).magic != HEADER_MAGIC {
+        return None;
+    }
+    Some(read(ptr))
+}
+
+/// Checks that the `map_size` bytes at `ptr` start with a header matching `conf`
+pub(crate) unsafe fn validate(
+    ptr: *const u8,
+    map_size: usize,
+    conf: HeaderConf,
+) -> Result<(), ShmemError> {
+    if map_size < HEADER_LEN {
+        return Err(ShmemError::HeaderInvalid);
+    }
+    let raw = raw(ptr);
+    if raw.magic != HEADER_MAGIC {
+        return Err(ShmemError::HeaderInvalid);
+    }
+    if raw.format_version != HEADER_FORMAT_VERSION || raw.header_len as usize != HEADER_LEN {
+        return Err(ShmemError::HeaderVersionMismatch(raw.format_version));
+    }
+    if (raw.payload_len as usize).saturating_add(HEADER_LEN) > map_size {
+        return Err(ShmemError::HeaderSizeMismatch {
+            expected: raw.payload_len as usize,
+            found: map_size - HEADER_LEN,
+        });
+    }
+    if raw.schema_id != conf.schema_id || raw.schema_version != conf.schema_version {
+        return Err(ShmemError::SchemaMismatch {
+            id: raw.schema_id,
+            version: raw.schema_version,
+        });
+    }
+    Ok(())
+}
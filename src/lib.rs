@@ -9,18 +9,29 @@
 )))]
 compile_error!("shared_memory isnt implemented for this platform...");
 
-use std::fs::{File, OpenOptions};
-use std::io::{ErrorKind, Read, Write};
+use std::io::ErrorKind;
 
-use std::fs::remove_file;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 mod error;
 mod event;
+mod flink;
+mod header;
+mod list;
 mod locks;
+mod reclaim;
+mod refcount;
+mod republish;
+mod wait;
 
 pub use error::*;
 pub use event::*;
+pub use header::ShmemHeader;
+pub use list::{list_segments, SegmentInfo};
+pub use reclaim::{reclaim_stale, Reclaimed};
+pub use refcount::CleanupPolicy;
+pub use wait::RetryPolicy;
 
 #[cfg(target_os = "windows")]
 mod windows;
@@ -31,6 +42,13 @@ use windows as os_impl;
 mod unix;
 #[cfg(any(target_os = "freebsd", target_os = "linux", target_os = "macos"))]
 use crate::unix as os_impl;
+#[cfg(any(target_os = "freebsd", target_os = "linux", target_os = "macos"))]
+pub use unix::{Advice, MapOptions, Protection, Seals, ShmemStat, ShmemWindow};
+
+#[cfg(target_os = "linux")]
+mod memfd;
+#[cfg(target_os = "linux")]
+pub use unix::{HugePageSize, NumaPolicy};
 
 #[cfg(feature = "tracing")]
 pub(crate) use tracing::{debug, trace};
@@ -51,23 +69,42 @@ macro_rules! warn (($($tt:tt)*) => {{}});
 #[cfg_attr(not(feature = "tracing"), macro_export)]
 macro_rules! error (($($tt:tt)*) => {{}});
 
+/// Suffix of the next default os_id of this process
+static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns an os_id made of `prefix`, the current pid and a counter
+fn next_os_id(prefix: &str) -> String {
+    format!(
+        "{}_{:X}_{:X}",
+        prefix,
+        std::process::id(),
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
 #[derive(Clone, Default)]
 /// Struct used to configure different parameters before creating a shared memory mapping
 pub struct ShmemConf {
     owner: bool,
     os_id: Option<String>,
     overwrite_flink: bool,
+    force_replace: bool,
     flink_path: Option<PathBuf>,
     size: usize,
+    header: Option<header::HeaderConf>,
+    cleanup: CleanupPolicy,
+    read_only: bool,
+    attached: bool,
+    retry: RetryPolicy,
     ext: os_impl::ShmemConfExt,
 }
 impl Drop for ShmemConf {
     fn drop(&mut self) {
         // Delete the flink if we are the owner of the mapping
         if self.owner {
+            // Keeps the flink if it was republished for another mapping
             if let Some(flink_path) = self.flink_path.as_ref() {
-                debug!("Deleting file link {}", flink_path.to_string_lossy());
-                let _ = remove_file(flink_path);
+                flink::remove(flink_path, self.os_id.as_deref(), &self.ext);
             }
         }
     }
@@ -80,7 +117,7 @@ impl ShmemConf {
     }
     /// Provide a specific os identifier for the mapping
     ///
-    /// When not specified, get the current pid
+    /// When not specified, an id made of the current pid and a counter is used
     pub fn id<S: AsRef<str>>(mut self, os_id: S) -> Self {
         self.os_id = Some(String::from(os_id.as_ref()));
         self
@@ -92,10 +129,24 @@ impl ShmemConf {
         self
     }
 
+    /// Makes `open_or_create()` replace an existing mapping and flink instead of opening them
+    ///
+    /// This is meant to get rid of objects left behind by a process that crashed. Replacing is retried as many times as
+    /// `retry()` allows, `MappingIdExists` is returned when the mapping keeps coming back. On Windows, mappings that
+    /// another process still has open cannot be replaced.
+    pub fn force_replace(mut self) -> Self {
+        self.force_replace = true;
+        self
+    }
+
     /// Create the shared memory mapping with a file link
     ///
     /// This creates a file on disk that contains the unique os_id for the mapping.
     /// This can be useful when application want to rely on filesystems to share mappings
+    ///
+    /// `open()` fails with `FlinkBackendMismatch` when the flink was written for another backend than the configured
+    /// one, and with `FlinkStale` when the mapping was recreated under the same os_id since the flink was written.
+    /// The size recorded in the flink is the one the mapping was created with, it may have been resized since.
     pub fn flink<S: AsRef<Path>>(mut self, path: S) -> Self {
         self.flink_path = Some(PathBuf::from(path.as_ref()));
         self
@@ -119,11 +170,22 @@ impl ShmemConf {
             }
         }
 
+        if self.header.is_some() && !self.ext.supports_header() {
+            return Err(ShmemError::HeaderNotSupported);
+        }
+
+        // Make room for the header in front of the payload
+        let map_size = match self.header {
+            Some(_) => self.size + header::HEADER_LEN,
+            None => self.size,
+        };
+
         // Create the mapping
         let mapping = match self.os_id {
             None => loop {
-                let cur_id = format!("/shmem_{:X}", std::process::id());
-                match os_impl::create_mapping(&cur_id, self.size) {
+                // A process that crashed with our pid may have left its mappings behind, try the next id
+                let cur_id = next_os_id("/shmem");
+                match os_impl::create_mapping(&cur_id, map_size, &self.ext) {
                     Err(ShmemError::MappingIdExists) => continue,
                     Ok(m) => break m,
                     Err(e) => {
@@ -131,41 +193,34 @@ impl ShmemConf {
                     }
                 };
             },
-            Some(ref specific_id) => os_impl::create_mapping(specific_id, self.size)?,
+            Some(ref specific_id) => os_impl::create_mapping(specific_id, map_size, &self.ext)?,
         };
         debug!("Created shared memory mapping '{}'", mapping.unique_id);
 
+        if let Some(header) = self.header {
+            unsafe {
+                header::write(
+                    mapping.as_mut_ptr(),
+                    header,
+                    mapping.map_size - header::HEADER_LEN,
+                    mapping.recorded_base(),
+                )
+            };
+        }
+
         // Create flink
         if let Some(ref flink_path) = self.flink_path {
             debug!("Creating file link that points to mapping");
-            let mut open_options: OpenOptions = OpenOptions::new();
-            open_options.write(true);
-
-            if self.overwrite_flink {
-                open_options.create(true).truncate(true);
-            } else {
-                open_options.create_new(true);
-            }
-
-            #[cfg(target_os = "linux")]
-            {
-                use std::os::unix::fs::OpenOptionsExt;
-                open_options.custom_flags((libc::S_IRWXU | libc::S_IRWXG | libc::S_IRWXO) as i32);
-            }
-
-            match open_options.open(flink_path) {
-                Ok(mut f) => {
-                    // write the shmem uid asap
-                    if let Err(e) = f.write(mapping.unique_id.as_bytes()) {
-                        let _ = std::fs::remove_file(flink_path);
-                        return Err(ShmemError::LinkWriteFailed(e));
-                    }
-                }
-                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
-                    return Err(ShmemError::LinkExists)
-                }
-                Err(e) => return Err(ShmemError::LinkCreateFailed(e)),
-            }
+            let header = self
+                .header
+                .map(|_| unsafe { header::read(mapping.as_mut_ptr()) });
+            let flink = flink::Flink::new(
+                &mapping.unique_id,
+                mapping.map_size - self.header.map_or(0, |_| header::HEADER_LEN),
+                flink::generation(&mapping, header),
+                &self.ext,
+            );
+            flink::write(flink_path, &flink, self.overwrite_flink, &self.ext)?;
 
             debug!(
                 "Created file link '{}' with id '{}'",
@@ -174,61 +229,164 @@ impl ShmemConf {
             );
         }
 
-        self.owner = true;
+        // Backing files are only deleted once ownership is taken
+        self.owner = mapping.is_owner();
         self.size = mapping.map_size;
+        self.os_id = Some(mapping.unique_id.clone());
 
-        Ok(Shmem {
+        let mut shmem = Shmem {
             config: self,
             mapping,
-        })
+        };
+        shmem.attach()?;
+        Ok(shmem)
+    }
+
+    /// Creates a new mapping or opens it if it already exists
+    ///
+    /// The returned `ShmemOrigin` tells whether this call created the mapping, in which case the caller is
+    /// responsible for initializing its content. Races with other processes creating, opening or dropping
+    /// the same os_id or flink are handled by retrying.
+    ///
+    /// An opened mapping may still be being initialized by its creator. With `ShmemConf::header()`, creators call
+    /// `Shmem::set_initialized()` once the content is ready and openers check `Shmem::is_initialized()`.
+    pub fn open_or_create(mut self) -> Result<(Shmem, ShmemOrigin)> {
+        if self.force_replace {
+            self.overwrite_flink = true;
+        }
+
+        let mut replaced = 0;
+        loop {
+            match self.clone().create() {
+                Ok(m) => return Ok((m, ShmemOrigin::Created)),
+                Err(ShmemError::MappingIdExists) if self.force_replace => {
+                    // Default os_ids are unique, there is nothing we could replace
+                    let os_id = match self.os_id.as_ref() {
+                        Some(v) => v,
+                        None => return Err(ShmemError::MappingIdExists),
+                    };
+                    // Someone keeps creating it, or the name is only freed along with its last handle
+                    if replaced > self.retry.attempts {
+                        return Err(ShmemError::MappingIdExists);
+                    }
+                    replaced += 1;
+                    debug!("Replacing existing mapping '{}'", os_id);
+                    os_impl::unlink_mapping(os_id, &self.ext)?;
+                    continue;
+                }
+                Err(ShmemError::MappingIdExists) | Err(ShmemError::LinkExists) => {}
+                Err(e) => return Err(e),
+            };
+
+            match self.clone().open() {
+                Ok(m) => return Ok((m, ShmemOrigin::Opened)),
+                // The mapping went away between our create() and open(), try to create it again
+                Err(ShmemError::LinkOpenFailed(e)) if e.kind() == ErrorKind::NotFound => {}
+                Err(ShmemError::MapOpenFailed(e))
+                    if self.flink_path.is_none() && e == os_impl::MAP_NOT_FOUND => {}
+                Err(e) => return Err(e),
+            };
+            debug!("Mapping disappeared while opening it, retrying");
+        }
     }
 
     /// Opens an existing mapping using the current configuration
-    pub fn open(mut self) -> Result<Shmem> {
+    pub fn open(self) -> Result<Shmem> {
+        self.open_inner(false)
+    }
+
+    /// Opens an existing mapping in read-only mode using the current configuration
+    ///
+    /// The mapping is opened and mapped without write access, any attempt to write through
+    /// the returned handle's pointer will fault.
+    pub fn open_read_only(self) -> Result<ReadOnlyShmem> {
+        Ok(ReadOnlyShmem {
+            inner: self.open_inner(true)?,
+        })
+    }
+
+    fn open_inner(mut self, read_only: bool) -> Result<Shmem> {
         // Must at least have a flink or an os_id
         if self.flink_path.is_none() && self.os_id.is_none() {
             debug!("Open called with no file link or unique id...");
             return Err(ShmemError::NoLinkOrOsId);
         }
+        if self.header.is_some() && !self.ext.supports_header() {
+            return Err(ShmemError::HeaderNotSupported);
+        }
 
-        let mut flink_uid = String::new();
         let mut retry = 0;
         loop {
-            let unique_id = if let Some(ref unique_id) = self.os_id {
-                retry = 5;
-                unique_id.as_str()
-            } else {
-                let flink_path = self.flink_path.as_ref().unwrap();
-                debug!(
-                    "Open shared memory from file link {}",
-                    flink_path.to_string_lossy()
-                );
-                let mut f = match File::open(flink_path) {
-                    Ok(f) => f,
-                    Err(e) => return Err(ShmemError::LinkOpenFailed(e)),
-                };
-                flink_uid.clear();
-                if let Err(e) = f.read_to_string(&mut flink_uid) {
-                    return Err(ShmemError::LinkReadFailed(e));
+            let flink = match self.flink_path {
+                Some(ref flink_path) => {
+                    debug!(
+                        "Open shared memory from file link {}",
+                        flink_path.to_string_lossy()
+                    );
+                    match flink::read(flink_path, &self.ext) {
+                        Ok(v) => Some(v),
+                        // The os_id is enough to open the mapping
+                        Err(ShmemError::LinkOpenFailed(e))
+                            if self.os_id.is_some() && e.kind() == ErrorKind::NotFound =>
+                        {
+                            None
+                        }
+                        // The creator may not have finished writing the flink
+                        Err(ShmemError::LinkReadFailed(e))
+                            if e.kind() == ErrorKind::InvalidData
+                                && retry < self.retry.attempts =>
+                        {
+                            retry += 1;
+                            std::thread::sleep(self.retry.delay);
+                            continue;
+                        }
+                        Err(e) => return Err(e),
+                    }
+                }
+                None => None,
+            };
+
+            let unique_id = match (self.os_id.as_ref(), flink.as_ref()) {
+                (Some(os_id), Some(flink)) if *os_id != flink.os_id => {
+                    return Err(ShmemError::FlinkInvalidOsId)
                 }
-                flink_uid.as_str()
+                (Some(os_id), _) => os_id.clone(),
+                (None, Some(flink)) => flink.os_id.clone(),
+                (None, None) => return Err(ShmemError::NoLinkOrOsId),
             };
+            // The flink must not decide how the os_id is opened
+            if let Some(backend) = flink.as_ref().and_then(|f| f.backend.as_ref()) {
+                if backend != self.ext.backend_name() {
+                    return Err(ShmemError::FlinkBackendMismatch(backend.clone()));
+                }
+            }
 
-            match os_impl::open_mapping(unique_id, self.size, &self.ext) {
+            match os_impl::open_mapping(&unique_id, self.size, read_only, &self.ext) {
                 Ok(m) => {
                     self.size = m.map_size;
                     self.owner = false;
+                    self.read_only = read_only;
+                    self.os_id = Some(unique_id);
 
-                    return Ok(Shmem {
+                    let mut shmem = Shmem {
                         config: self,
                         mapping: m,
-                    });
+                    };
+                    shmem.validate_header()?;
+                    shmem.map_at_recorded_base()?;
+                    if let Some(ref flink) = flink {
+                        shmem.validate_flink(flink)?;
+                    }
+                    shmem.attach()?;
+                    return Ok(shmem);
                 }
                 // If we got this failing os_id from the flink, try again in case the shmem owner didnt write the full
                 // unique_id to the file
-                Err(ShmemError::MapOpenFailed(_)) if self.os_id.is_none() && retry < 5 => {
+                Err(ShmemError::MapOpenFailed(_))
+                    if self.os_id.is_none() && retry < self.retry.attempts =>
+                {
                     retry += 1;
-                    std::thread::sleep(std::time::Duration::from_millis(50));
+                    std::thread::sleep(self.retry.delay);
                 }
                 Err(e) => return Err(e),
             }
@@ -236,6 +394,15 @@ impl ShmemConf {
     }
 }
 
+/// Tells whether `ShmemConf::open_or_create()` created the mapping or opened an existing one
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShmemOrigin {
+    /// The mapping was created by this call
+    Created,
+    /// The mapping already existed
+    Opened,
+}
+
 /// Structure used to extract information from an existing shared memory mapping
 pub struct Shmem {
     config: ShmemConf,
@@ -266,12 +433,16 @@ impl Shmem {
         self.config.flink_path.as_ref()
     }
     /// Returns the total size of the mapping
+    ///
+    /// This does not include the header configured with `ShmemConf::header()`
     pub fn len(&self) -> usize {
-        self.mapping.map_size
+        self.mapping.map_size - self.header_len()
     }
     /// Returns a raw pointer to the mapping
+    ///
+    /// This points right after the header configured with `ShmemConf::header()`
     pub fn as_ptr(&self) -> *mut u8 {
-        self.mapping.as_mut_ptr()
+        unsafe { self.mapping.as_mut_ptr().add(self.header_len()) }
     }
     /// Returns mapping as a byte slice
     /// # Safety
@@ -285,4 +456,46 @@ impl Shmem {
     pub unsafe fn as_slice_mut(&mut self) -> &mut [u8] {
         std::slice::from_raw_parts_mut(self.as_ptr(), self.len())
     }
+
+    /// Checks the header of an opened mapping
+    fn validate_header(&self) -> Result<()> {
+        match self.config.header {
+            Some(header) => unsafe {
+                header::validate(self.mapping.as_mut_ptr(), self.mapping.map_size, header)
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+/// Read-only handle to an existing shared memory mapping
+///
+/// Created through `ShmemConf::open_read_only()`, the underlying mapping has no write access
+pub struct ReadOnlyShmem {
+    inner: Shmem,
+}
+#[allow(clippy::len_without_is_empty)]
+impl ReadOnlyShmem {
+    /// Returns the OS unique identifier for the mapping
+    pub fn get_os_id(&self) -> &str {
+        self.inner.get_os_id()
+    }
+    /// Returns the flink path if present
+    pub fn get_flink_path(&self) -> Option<&PathBuf> {
+        self.inner.get_flink_path()
+    }
+    /// Returns the total size of the mapping
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+    /// Returns a raw read-only pointer to the mapping
+    pub fn as_ptr(&self) -> *const u8 {
+        self.inner.as_ptr()
+    }
+    /// Returns mapping as a byte slice
+    /// # Safety
+    /// This function is unsafe because it is impossible to ensure the range of bytes is immutable
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        self.inner.as_slice()
+    }
 }
@@ -0,0 +1,36 @@
+use std::time::SystemTime;
+
+use crate::{os_impl, Result, ShmemHeader};
+
+/// Description of an existing shared memory returned by `list_segments()`
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct SegmentInfo {
+    /// OS unique identifier of the mapping
+    pub os_id: String,
+    /// Size of the object, including the header if it has one
+    pub size: usize,
+    /// User id of the owner of the object
+    pub uid: u32,
+    /// Permission bits of the object
+    pub mode: u32,
+    /// When the object was last modified
+    pub modified: SystemTime,
+    /// Content of the header configured with `ShmemConf::header()`, `None` if the object has no header or cannot
+    /// be read by this process
+    pub header: Option<ShmemHeader>,
+}
+
+/// Lists the shared memory visible to this process whose os_id starts with `prefix`
+///
+/// An empty prefix lists everything, symbolic links and directories are skipped. Listing mappings is only supported
+/// on Linux, other platforms fail with `ListFailed`.
+pub fn list_segments<S: AsRef<str>>(prefix: S) -> Result<Vec<SegmentInfo>> {
+    let mut segments = Vec::new();
+    for unique_id in os_impl::list_segments(prefix.as_ref())? {
+        // Mappings deleted while we list them, or that we cannot open, are skipped
+        if let Some(info) = os_impl::segment_info(&unique_id) {
+            segments.push(info);
+        }
+    }
+    Ok(segments)
+}
@@ -0,0 +1,51 @@
+use std::ffi::CString;
+use std::os::fd::OwnedFd;
+
+use nix::sys::memfd::{memfd_create, MemFdCreateFlag};
+
+use crate::unix::{Backend, HugePageSize};
+use crate::{debug, trace, ShmemConf, ShmemError};
+
+impl ShmemConf {
+    /// Creates the mapping with `memfd_create()` instead of `shm_open()`
+    ///
+    /// The mapping has no name in the filesystem and is released once the last file descriptor
+    /// and mapping referencing it are gone. Other processes cannot open it through its os_id or a flink,
+    /// they must inherit or receive the file descriptor (see `Shmem::fd()` and `ShmemConf::open_fd()`).
+    ///
+    /// The descriptor is created with `FD_CLOEXEC`, clear it if the mapping must survive an `exec()`.
+    /// Sealing is allowed, see `Shmem::seal()`.
+    pub fn memfd(mut self) -> Self {
+        self.ext.backend = Backend::Memfd;
+        self
+    }
+}
+
+/// Creates an anonymous file that will back a mapping
+pub fn create_fd(unique_id: &str, huge_pages: Option<HugePageSize>) -> Result<OwnedFd, ShmemError> {
+    // The name is only used for debugging purposes, it shows up in /proc/<pid>/fd/
+    let name = match CString::new(unique_id.trim_start_matches('/')) {
+        Ok(v) => v,
+        Err(_) => return Err(ShmemError::MapCreateFailed(libc::EINVAL as _)),
+    };
+    let mut flags = MemFdCreateFlag::MFD_CLOEXEC | MemFdCreateFlag::MFD_ALLOW_SEALING;
+    match huge_pages {
+        Some(HugePageSize::Size2M) => {
+            flags |= MemFdCreateFlag::MFD_HUGETLB | MemFdCreateFlag::MFD_HUGE_2MB
+        }
+        Some(HugePageSize::Size1G) => {
+            flags |= MemFdCreateFlag::MFD_HUGETLB | MemFdCreateFlag::MFD_HUGE_1GB
+        }
+        None => {}
+    }
+
+    debug!("Creating anonymous file for mapping {}", unique_id);
+    match memfd_create(&name, flags) {
+        Ok(v) => {
+            trace!("memfd_create({:?}, {:X}) == {v:?}", name, flags);
+            Ok(v)
+        }
+        Err(e) if huge_pages.is_some() => Err(ShmemError::HugePagesUnavailable(e as u32)),
+        Err(e) => Err(ShmemError::MapCreateFailed(e as u32)),
+    }
+}
@@ -0,0 +1,116 @@
+use std::fs::{read_dir, remove_file};
+use std::path::{Path, PathBuf};
+
+use crate::{debug, flink, os_impl, Result, ShmemError};
+
+/// What `os_impl::reclaim_segment()` found behind an os_id
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum SegmentState {
+    /// There is no shared memory with this os_id
+    Missing,
+    /// The shared memory is in use or might be, it was left alone
+    Alive,
+    /// The shared memory was stale and got deleted
+    Reclaimed,
+}
+
+/// Shared memory and flinks deleted by `reclaim_stale()`
+#[derive(Clone, Default, PartialEq, Eq, Debug)]
+pub struct Reclaimed {
+    /// os_id of the deleted mappings
+    pub segments: Vec<String>,
+    /// Paths of the deleted flinks
+    pub flinks: Vec<PathBuf>,
+}
+
+/// Deletes the shared memory and flinks left behind by processes that died
+///
+/// A prefix that only has a slash at the start, like `"/myservice_"`, selects the mappings whose os_id starts with
+/// it. Any other prefix is a path, like `"/run/myservice/"` or `"/run/myservice/shmem_"`, and selects the flinks in
+/// that directory whose name starts with what follows the last slash. Listing mappings is only supported on Linux.
+///
+/// Every handle created by this crate holds a shared `flock()` on the mapping, a mapping nobody holds a lock on is
+/// only deleted if the pid that created it, taken from its header or from a default os_id, is not running anymore.
+/// Flinks are deleted along with their mapping or when their mapping is already gone. This is meant to be called
+/// once at start-up, the prefix must not match mappings or flinks that are meant to outlive their creator.
+pub fn reclaim_stale<S: AsRef<str>>(prefix: S) -> Result<Reclaimed> {
+    let prefix = prefix.as_ref();
+    let mut reclaimed = Reclaimed::default();
+
+    if !prefix.get(1..).unwrap_or("").contains('/') {
+        for unique_id in os_impl::list_segments(prefix)? {
+            if os_impl::reclaim_segment(&unique_id)? == SegmentState::Reclaimed {
+                debug!("Reclaimed stale mapping '{}'", unique_id);
+                reclaimed.segments.push(unique_id);
+            }
+        }
+        return Ok(reclaimed);
+    }
+
+    let (dir, name_prefix) = match prefix.strip_suffix('/') {
+        Some(dir) => (Path::new(dir), ""),
+        None => {
+            let path = Path::new(prefix);
+            (
+                path.parent().unwrap_or(Path::new("")),
+                path.file_name().and_then(|n| n.to_str()).unwrap_or(""),
+            )
+        }
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+
+    let ext = os_impl::ShmemConfExt::default();
+    let default_backend = ext.backend_name();
+    let entries = read_dir(dir).map_err(ShmemError::ListFailed)?;
+    for entry in entries {
+        let entry = entry.map_err(ShmemError::ListFailed)?;
+        let matches = entry
+            .file_name()
+            .to_str()
+            .is_some_and(|n| n.starts_with(name_prefix));
+        if !matches || !entry.file_type().is_ok_and(|t| t.is_file()) {
+            continue;
+        }
+
+        // Skip whatever does not look like a flink, including ones still being written, and backing files
+        let flink_path = entry.path();
+        let unique_id = match flink::read(&flink_path, &ext) {
+            Ok(flink)
+                if is_os_id(&flink.os_id)
+                    && flink
+                        .backend
+                        .as_deref()
+                        .is_none_or(|b| b == default_backend) =>
+            {
+                flink.os_id
+            }
+            _ => continue,
+        };
+        match os_impl::reclaim_segment(&unique_id)? {
+            SegmentState::Alive => continue,
+            SegmentState::Missing => {}
+            SegmentState::Reclaimed => {
+                debug!("Reclaimed stale mapping '{}'", unique_id);
+                reclaimed.segments.push(unique_id);
+            }
+        }
+        debug!("Deleting stale file link {}", flink_path.to_string_lossy());
+        if remove_file(&flink_path).is_ok() {
+            reclaimed.flinks.push(flink_path);
+        }
+    }
+
+    Ok(reclaimed)
+}
+
+/// Returns whether the content of a flink is an os_id
+fn is_os_id(content: &str) -> bool {
+    content.len() > 1
+        && content.len() < 256
+        && !content.chars().any(|c| c.is_control() || c.is_whitespace())
+        && (cfg!(windows) || (content.starts_with('/') && !content[1..].contains('/')))
+}
@@ -0,0 +1,105 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+use crate::{debug, header, Result, Shmem, ShmemConf};
+
+/// Decides which handle deletes the shared memory and its flink when it is dropped
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum CleanupPolicy {
+    /// The handle that created the mapping deletes it, see `Shmem::set_owner()`
+    #[default]
+    Owner,
+    /// The last handle attached to the mapping deletes it, in whichever process it lives
+    RefCount,
+}
+
+impl ShmemConf {
+    /// Sets which handle deletes the mapping and its flink when it is dropped
+    ///
+    /// With `CleanupPolicy::RefCount`, every `create()` and `open()` increments a counter stored in the header of the
+    /// shared memory and every drop decrements it. The handle that detaches last deletes the mapping, even if the
+    /// creator is long gone. Mappings without `ShmemConf::header()` get one with schema id 0 and version 0, openers
+    /// must use the same policy to find the counter.
+    ///
+    /// On unix, every handle also holds a shared `flock()` on the mapping. Processes that crash release it without
+    /// decrementing the counter, so the last handle is detected through the lock instead. The stale count is
+    /// discarded once every other handle detached or when a handle attaches to a mapping nobody else holds.
+    /// Read-only handles keep the mapping alive through the lock but are not counted.
+    pub fn cleanup(mut self, policy: CleanupPolicy) -> Self {
+        self.cleanup = policy;
+        if policy == CleanupPolicy::RefCount && self.header.is_none() {
+            self = self.header(0, 0);
+        }
+        self
+    }
+}
+
+impl Shmem {
+    /// Returns how many handles are attached to the mapping
+    ///
+    /// `None` unless the mapping uses `CleanupPolicy::RefCount`. This may include handles of processes that
+    /// crashed, see `ShmemConf::cleanup()`.
+    pub fn ref_count(&self) -> Option<u32> {
+        if self.config.cleanup != CleanupPolicy::RefCount {
+            return None;
+        }
+        Some(self.counter().load(Ordering::Acquire))
+    }
+
+    /// Registers a new handle with the reference count of the mapping
+    ///
+    /// Every handle holds the lock that tells `reclaim_stale()` the mapping is in use, whatever the policy.
+    pub(crate) fn attach(&mut self) -> Result<()> {
+        let alone = self.mapping.lock_attached()?;
+        self.config.attached = true;
+        if self.config.cleanup != CleanupPolicy::RefCount || self.config.read_only {
+            return Ok(());
+        }
+        if alone == Some(true) {
+            // Nobody else holds the mapping, counts left by crashed processes are stale
+            self.counter().store(1, Ordering::Release);
+        } else {
+            self.counter().fetch_add(1, Ordering::AcqRel);
+        }
+        Ok(())
+    }
+
+    /// Unregisters this handle and returns whether it was the last one attached
+    fn detach(&self) -> bool {
+        let prev = if self.config.read_only {
+            u32::MAX
+        } else {
+            let counter = self.counter();
+            let mut cur = counter.load(Ordering::Acquire);
+            // Never wraps around, the count may have been reset by a handle that attached alone
+            while let Err(v) = counter.compare_exchange_weak(
+                cur,
+                cur.saturating_sub(1),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                cur = v;
+            }
+            cur
+        };
+        self.mapping.is_last_attached().unwrap_or(prev <= 1)
+    }
+
+    fn counter(&self) -> &AtomicU32 {
+        unsafe { header::ref_count(self.mapping.as_mut_ptr()) }
+    }
+}
+
+impl Drop for Shmem {
+    fn drop(&mut self) {
+        // Handles that failed to open were never counted
+        if self.config.cleanup != CleanupPolicy::RefCount || !self.config.attached {
+            return;
+        }
+        let last = self.detach();
+        if last {
+            debug!("Last handle detached from '{}'", self.get_os_id());
+        }
+        // The flink and the mapping are deleted by their own drop when we are the owner
+        self.set_owner(last);
+    }
+}
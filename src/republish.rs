@@ -0,0 +1,83 @@
+use crate::{flink, next_os_id, ReadOnlyShmem, Result, Shmem, ShmemConf, ShmemError};
+
+impl ShmemConf {
+    /// Creates a new mapping and atomically points the flink at it
+    ///
+    /// Every call creates a mapping under a new os_id, made of the os_id given to `id()` (or `"/shmem"`) followed by
+    /// the pid and a counter, then replaces the flink. Readers opening the flink from then on get the new mapping,
+    /// the ones attached to a previous mapping notice it with `Shmem::is_stale()` and switch with `Shmem::reopen()`.
+    ///
+    /// Dropping the handle of a previous mapping does not delete the flink that now points to the new one. The
+    /// memory of a previous mapping is released once its last reader detaches.
+    pub fn republish(mut self) -> Result<Shmem> {
+        if self.flink_path.is_none() {
+            return Err(ShmemError::NoLinkOrOsId);
+        }
+        let prefix = self.os_id.take().unwrap_or_else(|| String::from("/shmem"));
+        self.overwrite_flink = true;
+
+        loop {
+            let mut conf = self.clone();
+            conf.os_id = Some(next_os_id(&prefix));
+            match conf.create() {
+                Err(ShmemError::MappingIdExists) => continue,
+                res => return res,
+            }
+        }
+    }
+}
+
+impl Shmem {
+    /// Returns whether the flink no longer points to this mapping
+    ///
+    /// This happens when the flink was republished with `ShmemConf::republish()` or deleted. Mappings without a
+    /// flink are never stale. This reads the flink every time, it does not open the mapping.
+    pub fn is_stale(&self) -> bool {
+        let flink_path = match self.config.flink_path.as_ref() {
+            Some(v) => v,
+            None => return false,
+        };
+        match flink::read(flink_path, &self.config.ext) {
+            Ok(flink) => flink.os_id != self.get_os_id() || self.validate_flink(&flink).is_err(),
+            Err(_) => true,
+        }
+    }
+
+    /// Opens the mapping the flink currently points to, with the same configuration as this handle
+    ///
+    /// Used to switch over to a mapping published with `ShmemConf::republish()`.
+    pub fn reopen(&self) -> Result<Shmem> {
+        self.reopen_conf().open_inner(false)
+    }
+
+    /// Returns the configuration to open the flink again
+    fn reopen_conf(&self) -> ShmemConf {
+        let mut conf = self.config.clone();
+        // Only the flink tells which mapping to open
+        if conf.flink_path.is_some() {
+            conf.os_id = None;
+        }
+        conf.owner = false;
+        conf.attached = false;
+        conf.size = 0;
+        conf
+    }
+}
+
+impl ReadOnlyShmem {
+    /// Returns whether the flink no longer points to this mapping
+    ///
+    /// See `Shmem::is_stale()`
+    pub fn is_stale(&self) -> bool {
+        self.inner.is_stale()
+    }
+
+    /// Opens the mapping the flink currently points to in read-only mode
+    ///
+    /// See `Shmem::reopen()`
+    pub fn reopen(&self) -> Result<ReadOnlyShmem> {
+        Ok(ReadOnlyShmem {
+            inner: self.inner.reopen_conf().open_inner(true)?,
+        })
+    }
+}
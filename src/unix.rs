@@ -1,229 +1,2623 @@
 use std::ffi::c_void;
+use std::fs::{remove_file, File, OpenOptions};
 use std::num::NonZeroUsize;
-use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
 use std::ptr::NonNull;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use nix::fcntl::OFlag;
-use nix::sys::mman::{mmap, munmap, shm_open, shm_unlink, MapFlags, ProtFlags};
+use nix::sys::mman::{
+    madvise, mlock, mmap, mmap_anonymous, mprotect, msync, munlock, munmap, shm_open, shm_unlink,
+    MapFlags, MmapAdvise, MsFlags, ProtFlags,
+};
+#[cfg(target_os = "linux")]
+use nix::sys::mman::{mremap, MRemapFlags};
 use nix::sys::stat::{fchmod, fstat, Mode};
-use nix::unistd::{close, ftruncate};
+use nix::unistd::ftruncate;
 
-use crate::ShmemError;
-use crate::{debug, trace};
+use crate::reclaim::SegmentState;
+use crate::{debug, header, trace};
+use crate::{ReadOnlyShmem, SegmentInfo, Shmem, ShmemConf, ShmemError, ShmemHeader};
+
+/// Kind of object backing a mapping
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Backend {
+    /// Named object created with shm_open()
+    #[default]
+    Shm,
+    /// Anonymous file created with memfd_create()
+    #[cfg(target_os = "linux")]
+    Memfd,
+    /// Regular file, the os_id is its path
+    File,
+    /// Anonymous shared memory that is only inherited through fork()
+    Anonymous,
+    /// Mapping opened from a file descriptor we were handed
+    Fd,
+}
+
+/// Size of the huge pages backing a mapping
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum HugePageSize {
+    /// 2 MiB pages
+    Size2M,
+    /// 1 GiB pages
+    Size1G,
+}
+#[cfg(target_os = "linux")]
+impl HugePageSize {
+    /// Returns the size of a page in bytes
+    pub fn bytes(self) -> usize {
+        match self {
+            HugePageSize::Size2M => 2 << 20,
+            HugePageSize::Size1G => 1 << 30,
+        }
+    }
+    fn map_flags(self) -> MapFlags {
+        MapFlags::MAP_HUGETLB
+            | match self {
+                HugePageSize::Size2M => MapFlags::MAP_HUGE_2MB,
+                HugePageSize::Size1G => MapFlags::MAP_HUGE_1GB,
+            }
+    }
+}
+
+/// NUMA memory policy deciding which nodes back the pages of a mapping
+#[cfg(target_os = "linux")]
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum NumaPolicy {
+    /// System default, pages are usually allocated on the node of the CPU that faults them in
+    Default,
+    /// Pages are only allocated on the given nodes
+    Bind(Vec<u32>),
+    /// Pages are spread across the given nodes
+    Interleave(Vec<u32>),
+    /// Pages are allocated on the given node when it has free memory
+    Preferred(u32),
+}
+#[cfg(target_os = "linux")]
+impl NumaPolicy {
+    /// Number of nodes that fit in the masks given to get_mempolicy()
+    const MAX_NODES: usize = 1024;
+    const MASK_BITS: usize = libc::c_ulong::BITS as usize;
+
+    /// Returns the mode and node mask to pass to mbind()
+    fn to_raw(&self) -> (libc::c_int, Vec<libc::c_ulong>) {
+        let (mode, nodes) = match self {
+            NumaPolicy::Default => return (libc::MPOL_DEFAULT, Vec::new()),
+            NumaPolicy::Bind(nodes) => (libc::MPOL_BIND, nodes.as_slice()),
+            NumaPolicy::Interleave(nodes) => (libc::MPOL_INTERLEAVE, nodes.as_slice()),
+            NumaPolicy::Preferred(node) => (libc::MPOL_PREFERRED, std::slice::from_ref(node)),
+        };
+        let max_node = nodes.iter().max().copied().unwrap_or(0) as usize;
+        let mut mask = vec![0; max_node / Self::MASK_BITS + 1];
+        for node in nodes.iter().map(|n| *n as usize) {
+            mask[node / Self::MASK_BITS] |= 1 << (node % Self::MASK_BITS);
+        }
+        (mode, mask)
+    }
+
+    /// Builds a policy from what get_mempolicy() returned
+    fn from_raw(mode: libc::c_int, mask: &[libc::c_ulong]) -> Self {
+        let nodes: Vec<u32> = (0..mask.len() * Self::MASK_BITS)
+            .filter(|n| mask[n / Self::MASK_BITS] & (1 << (n % Self::MASK_BITS)) != 0)
+            .map(|n| n as u32)
+            .collect();
+        // Ignore the mode flags
+        match mode & 0xFF {
+            libc::MPOL_BIND => NumaPolicy::Bind(nodes),
+            libc::MPOL_INTERLEAVE | MPOL_WEIGHTED_INTERLEAVE => NumaPolicy::Interleave(nodes),
+            libc::MPOL_PREFERRED | MPOL_PREFERRED_MANY if !nodes.is_empty() => {
+                NumaPolicy::Preferred(nodes[0])
+            }
+            _ => NumaPolicy::Default,
+        }
+    }
+}
+#[cfg(target_os = "linux")]
+const MPOL_PREFERRED_MANY: libc::c_int = 5;
+#[cfg(target_os = "linux")]
+const MPOL_WEIGHTED_INTERLEAVE: libc::c_int = 6;
+#[cfg(target_os = "linux")]
+const MPOL_MF_MOVE: libc::c_uint = 1 << 1;
+#[cfg(target_os = "linux")]
+const MPOL_F_ADDR: libc::c_ulong = 1 << 1;
+
+/// Mapping options that took effect, see `Shmem::map_options()`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct MapOptions {
+    /// Every page was resident right after mapping (`ShmemConf::populate()`)
+    pub populated: bool,
+    /// The whole mapping is locked in memory (`ShmemConf::lock()`)
+    pub locked: bool,
+    /// No swap space is reserved for the mapping (`ShmemConf::no_reserve()`)
+    pub no_reserve: bool,
+}
+
+/// Status of the object backing a mapping, see `Shmem::stat()`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ShmemStat {
+    /// User id of the owner of the object
+    pub uid: u32,
+    /// Group id of the object
+    pub gid: u32,
+    /// Permission bits of the object
+    pub mode: u32,
+    /// Number of names the object has
+    pub nlink: u64,
+    /// Size of the object, including the header if it has one
+    pub size: usize,
+    /// Number of 512 byte blocks allocated to the object
+    pub blocks: u64,
+    /// When the object was created, `None` if the filesystem does not record it
+    pub created: Option<SystemTime>,
+    /// When the content of the object was last modified
+    pub modified: SystemTime,
+    /// When the content of the object was last accessed
+    pub accessed: SystemTime,
+    /// When the status of the object last changed
+    pub changed: SystemTime,
+    /// The os_id no longer leads to this object, new processes cannot open it anymore
+    pub unlinked: bool,
+}
+
+/// Set of seals preventing changes to shared memory, see `Shmem::seal()`
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Seals {
+    /// The size of the shared memory cannot be reduced
+    pub no_shrink: bool,
+    /// The size of the shared memory cannot be increased
+    pub no_grow: bool,
+    /// The content of the shared memory cannot be modified
+    pub no_write: bool,
+    /// The shared memory has no write permission bits, which keeps processes from opening it for writing
+    ///
+    /// This is what `Shmem::seal()` falls back to when the kernel cannot seal the object. It is weaker than the other
+    /// seals, descriptors that were already opened for writing can still write to and resize the object, and its
+    /// owner can give the permissions back.
+    pub write_protected: bool,
+}
+impl Seals {
+    /// Every seal the kernel enforces
+    pub const ALL: Seals = Seals {
+        no_shrink: true,
+        no_grow: true,
+        no_write: true,
+        write_protected: false,
+    };
+
+    /// Returns whether every seal of `other` is also in `self`
+    pub fn contains(&self, other: Seals) -> bool {
+        (self.no_shrink || !other.no_shrink)
+            && (self.no_grow || !other.no_grow)
+            && (self.no_write || !other.no_write)
+            && (self.write_protected || !other.write_protected)
+    }
+    fn is_empty(&self) -> bool {
+        *self == Seals::default()
+    }
+}
+
+/// Returns the seals applied to the object behind `fd`
+fn fd_seals(fd: BorrowedFd<'_>) -> nix::Result<Seals> {
+    let mut seals = Seals::default();
+
+    #[cfg(target_os = "linux")]
+    {
+        let res = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_GET_SEALS) };
+        trace!("fcntl({:?}, F_GET_SEALS) == {:X}", fd, res);
+        if res >= 0 {
+            seals.no_shrink = res & libc::F_SEAL_SHRINK != 0;
+            seals.no_grow = res & libc::F_SEAL_GROW != 0;
+            seals.no_write = res & libc::F_SEAL_WRITE != 0;
+        }
+    }
+
+    let mode = fstat(fd.as_raw_fd())?.st_mode;
+    seals.write_protected = mode & 0o222 == 0;
+    Ok(seals)
+}
+
+/// Access allowed to a range of a mapping, see `Shmem::protect()`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Protection {
+    /// Pages can be read and written
+    ReadWrite,
+    /// Writing to the pages raises `SIGSEGV`
+    ReadOnly,
+    /// Any access to the pages raises `SIGSEGV`
+    None,
+}
+impl Protection {
+    fn prot_flags(self) -> ProtFlags {
+        match self {
+            Protection::ReadWrite => ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+            Protection::ReadOnly => ProtFlags::PROT_READ,
+            Protection::None => ProtFlags::PROT_NONE,
+        }
+    }
+}
+
+/// Hint about how a range of a mapping is going to be used, see `Shmem::advise()`
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Advice {
+    /// No special treatment
+    Normal,
+    /// The pages will be accessed soon, read them in ahead of time
+    WillNeed,
+    /// The pages will not be accessed soon, they can be released from this process
+    DontNeed,
+    /// The pages will be accessed in order, read ahead aggressively
+    Sequential,
+    /// The pages will be accessed in random order, do not read ahead
+    Random,
+    /// Back the pages with transparent huge pages when possible
+    #[cfg(target_os = "linux")]
+    HugePage,
+}
+impl Advice {
+    fn madvise_flag(self) -> MmapAdvise {
+        match self {
+            Advice::Normal => MmapAdvise::MADV_NORMAL,
+            Advice::WillNeed => MmapAdvise::MADV_WILLNEED,
+            Advice::DontNeed => MmapAdvise::MADV_DONTNEED,
+            Advice::Sequential => MmapAdvise::MADV_SEQUENTIAL,
+            Advice::Random => MmapAdvise::MADV_RANDOM,
+            #[cfg(target_os = "linux")]
+            Advice::HugePage => MmapAdvise::MADV_HUGEPAGE,
+        }
+    }
+}
+
+/// Where a mapping should be placed in the address space
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Placement {
+    /// Preferred address, the mapping ends up somewhere else if it is taken
+    Hint(usize),
+    /// Required address, failing with `ShmemError::AddressInUse` if it is taken
+    Fixed(usize),
+}
+impl Placement {
+    fn addr(self) -> usize {
+        match self {
+            Placement::Hint(addr) | Placement::Fixed(addr) => addr,
+        }
+    }
+}
 
 #[derive(Clone, Default)]
-pub struct ShmemConfExt;
+pub struct ShmemConfExt {
+    pub(crate) backend: Backend,
+    #[cfg(target_os = "linux")]
+    pub(crate) huge_pages: Option<HugePageSize>,
+    mode: Option<u32>,
+    flink_mode: Option<u32>,
+    flink_owner: Option<u32>,
+    group: Option<u32>,
+    ignore_umask: bool,
+    placement: Option<Placement>,
+    window: Option<(usize, usize)>,
+    ring_buffer: bool,
+    guard_pages: bool,
+    required_seals: Seals,
+    #[cfg(target_os = "linux")]
+    populate: bool,
+    #[cfg(target_os = "linux")]
+    no_reserve: bool,
+    lock: bool,
+    #[cfg(target_os = "linux")]
+    numa_policy: Option<NumaPolicy>,
+}
+
+impl ShmemConfExt {
+    /// Permissions of created mappings
+    fn mode(&self) -> Mode {
+        Mode::from_bits_truncate(self.mode.unwrap_or(0o666) as _)
+    }
+
+    /// Name of the backend recorded in flinks
+    pub(crate) fn backend_name(&self) -> &'static str {
+        match self.backend {
+            Backend::Shm => "shm",
+            #[cfg(target_os = "linux")]
+            Backend::Memfd => "memfd",
+            Backend::File => "file",
+            Backend::Anonymous => "anonymous",
+            Backend::Fd => "fd",
+        }
+    }
+
+    /// Permissions of created flinks
+    pub(crate) fn flink_mode(&self) -> u32 {
+        self.flink_mode.unwrap_or(0o666)
+    }
+
+    /// Whether the mapping layout leaves room for a header
+    ///
+    /// Ring buffers wrap around over the start of the object and windows do not start with it.
+    pub(crate) fn supports_header(&self) -> bool {
+        !self.ring_buffer && self.window.is_none()
+    }
+
+    /// Extra mmap() flags requested by the configuration
+    fn map_flags(&self) -> MapFlags {
+        #[allow(unused_mut)]
+        let mut flags = MapFlags::empty();
+        #[cfg(target_os = "linux")]
+        {
+            if self.populate {
+                flags |= MapFlags::MAP_POPULATE;
+            }
+            if self.no_reserve {
+                flags |= MapFlags::MAP_NORESERVE;
+            }
+        }
+        flags
+    }
+
+    /// Applies the configured group and permissions to a newly created object
+    fn apply_permissions(&self, fd: BorrowedFd<'_>, mode: Mode) -> nix::Result<()> {
+        // The mode given at creation is filtered by the umask, force it if requested
+        if self.ignore_umask {
+            trace!("fchmod({:?}, {:o})", fd, mode);
+            fchmod(fd.as_raw_fd(), mode)?;
+        }
+
+        if let Some(gid) = self.group {
+            trace!("fchown({:?}, -1, {})", fd, gid);
+            if unsafe { libc::fchown(fd.as_raw_fd(), libc::uid_t::MAX, gid as _) } != 0 {
+                return Err(nix::Error::last());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Applies the configured group and permissions to a newly created flink
+    pub(crate) fn apply_flink_permissions(&self, flink: &File) -> std::io::Result<()> {
+        self.apply_permissions(
+            flink.as_fd(),
+            Mode::from_bits_truncate(self.flink_mode() as _),
+        )?;
+        Ok(())
+    }
+
+    /// Checks that a flink we opened belongs to the user required by `ShmemConf::flink_owner()`
+    pub(crate) fn check_flink_owner(&self, flink: &File) -> Result<(), ShmemError> {
+        use std::os::unix::fs::MetadataExt;
+
+        let uid = match self.flink_owner {
+            Some(v) => v,
+            None => return Ok(()),
+        };
+        let found = flink.metadata().map_err(ShmemError::LinkReadFailed)?.uid();
+        if found != uid {
+            debug!("Flink is owned by uid {} instead of {}", found, uid);
+            return Err(ShmemError::FlinkOwnerMismatch(found));
+        }
+        Ok(())
+    }
+}
+
+pub struct MapData {
+    //On linux, you must shm_unlink() the object created for the mapping. It wont disappear automatically.
+    owner: bool,
+
+    //What kind of object backs this mapping
+    backend: Backend,
+
+    //File descriptor to our open mapping, anonymous mappings have none
+    map_fd: Option<OwnedFd>,
+    //Separate open file description holding a shared lock until the creator attaches, see lock_attached()
+    creation_lock: Option<OwnedFd>,
+
+    //Shared mapping uid
+    pub unique_id: String,
+    //Total size of the mapping
+    pub map_size: usize,
+    //Pointer to the first address of our mapping
+    pub map_ptr: NonNull<c_void>,
+    //Page protection used when (re)mapping
+    map_prot: ProtFlags,
+    //Size of the pages backing the mapping
+    page_size: usize,
+    //Address the mapping was placed at on request, it must not move
+    placement: Option<Placement>,
+    //Distance between map_ptr and the data, windows do not always start on a page boundary
+    data_offset: usize,
+    //Offset of the data in the backing object when only a window of it is mapped
+    window_offset: Option<usize>,
+    //Whether the data is mapped a second time right after itself
+    ring_buffer: bool,
+    //Size of the inaccessible guards before and after the mapping
+    guard_len: usize,
+    //Mapping options that took effect
+    options: MapOptions,
+}
+
+impl MapData {
+    pub fn as_mut_ptr(&self) -> *mut u8 {
+        unsafe { self.map_ptr.byte_add(self.data_offset).as_ptr() as _ }
+    }
+
+    /// Number of bytes actually mapped
+    fn mapped_len(&self) -> usize {
+        let len = self.data_offset + self.map_size;
+        // Ring buffers are mapped twice
+        if self.ring_buffer {
+            len * 2
+        } else {
+            len
+        }
+    }
+
+    /// Returns the whole range we reserved in the address space, guard pages included
+    fn reserved_range(&self) -> (NonNull<c_void>, usize) {
+        if self.guard_len == 0 {
+            return (self.map_ptr, self.mapped_len());
+        }
+        (
+            unsafe { self.map_ptr.byte_sub(self.guard_len) },
+            self.mapped_len().next_multiple_of(self.page_size) + 2 * self.guard_len,
+        )
+    }
+
+    /// Describes how the mapping is laid out in the address space
+    fn layout(&self) -> Layout {
+        Layout {
+            ring_buffer: self.ring_buffer,
+            guard_len: self.guard_len,
+            page_size: self.page_size,
+        }
+    }
+}
+
+/// Shared memory teardown for linux
+impl Drop for MapData {
+    ///Takes care of properly closing the SharedMem (munmap(), shmem_unlink(), close())
+    fn drop(&mut self) {
+        //Unmap memory
+        let (reserved_ptr, reserved_len) = self.reserved_range();
+        trace!(
+            "munmap(map_ptr:{:p},map_size:{})",
+            reserved_ptr,
+            reserved_len
+        );
+        if let Err(_e) = unsafe { munmap(reserved_ptr, reserved_len) } {
+            debug!("Failed to munmap() shared memory mapping : {}", _e);
+        };
+
+        //unlink shmem if we created it
+        if self.owner {
+            match self.backend {
+                Backend::Shm => {
+                    debug!("Deleting persistent mapping");
+                    trace!("shm_unlink({})", self.unique_id.as_str());
+                    if let Err(_e) = shm_unlink(self.unique_id.as_str()) {
+                        debug!("Failed to shm_unlink() shared memory : {}", _e);
+                    };
+                }
+                Backend::File => {
+                    debug!("Deleting backing file");
+                    trace!("unlink({})", self.unique_id.as_str());
+                    if let Err(_e) = remove_file(self.unique_id.as_str()) {
+                        debug!("Failed to remove backing file : {}", _e);
+                    };
+                }
+                _ => {}
+            }
+        }
+
+        //The file descriptor is closed when map_fd is dropped
+        trace!("close({:?})", self.map_fd);
+    }
+}
+
+impl MapData {
+    /// Applies a NUMA policy to the whole mapping, migrating the pages we already have
+    #[cfg(target_os = "linux")]
+    fn mbind(&self, policy: &NumaPolicy) -> Result<(), ShmemError> {
+        let (mode, mask) = policy.to_raw();
+        let max_node = mask.len() * NumaPolicy::MASK_BITS + 1;
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_mbind,
+                self.map_ptr.as_ptr(),
+                self.mapped_len(),
+                mode,
+                if mask.is_empty() {
+                    std::ptr::null()
+                } else {
+                    mask.as_ptr()
+                },
+                if mask.is_empty() { 0 } else { max_node },
+                MPOL_MF_MOVE,
+            )
+        };
+        trace!(
+            "mbind({:p}, {}, {}, {:?}, {}, MPOL_MF_MOVE) == {}",
+            self.map_ptr,
+            self.mapped_len(),
+            mode,
+            mask,
+            max_node,
+            res
+        );
+        if res != 0 {
+            return Err(ShmemError::NumaPolicyFailed(nix::Error::last() as u32));
+        }
+        Ok(())
+    }
+
+    pub fn is_owner(&self) -> bool {
+        self.owner
+    }
+
+    pub fn set_owner(&mut self, is_owner: bool) -> bool {
+        let prev_val = self.owner;
+        self.owner = is_owner;
+        prev_val
+    }
+
+    /// Holds a shared `flock()` on the object for as long as the mapping is attached
+    ///
+    /// This tells `is_last_attached()` and `reclaim_segment()` that the object is in use. Returns whether no other
+    /// handle held the lock, `None` when the object cannot be locked.
+    ///
+    /// A failed attempt to turn a shared lock into an exclusive one releases it, so the lock the creator takes right
+    /// after creating the object lives on another open file description. It is released once `map_fd` holds its own.
+    pub fn lock_attached(&mut self) -> Result<Option<bool>, ShmemError> {
+        // Descriptors we were handed share their lock with the process that sent them
+        let fd = match self.map_fd.as_ref() {
+            Some(fd) if self.backend != Backend::Fd => fd.as_raw_fd(),
+            _ => return Ok(None),
+        };
+        let res = unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) };
+        trace!("flock({}, LOCK_EX | LOCK_NB) == {}", fd, res);
+        let alone = match res {
+            0 => true,
+            _ if nix::Error::last() == nix::Error::EWOULDBLOCK => false,
+            _ => {
+                debug!("Mapping does not support flock() : {}", nix::Error::last());
+                return Ok(None);
+            }
+        };
+        // Waits for a handle that is deleting the object in is_last_attached()
+        let res = unsafe { libc::flock(fd, libc::LOCK_SH) };
+        trace!("flock({}, LOCK_SH) == {}", fd, res);
+        if res != 0 {
+            return Ok(None);
+        }
+
+        self.creation_lock = None;
+
+        // The last handle deleted the object while we were opening it
+        if matches!(self.backend, Backend::Shm | Backend::File)
+            && fstat(fd).map(|s| s.st_nlink == 0).unwrap_or(false)
+        {
+            debug!("Mapping '{}' was deleted while attaching", self.unique_id);
+            return Err(ShmemError::MapOpenFailed(MAP_NOT_FOUND));
+        }
+        Ok(Some(alone))
+    }
+
+    /// Returns whether the os_id no longer leads to the object described by `st`
+    fn is_unlinked(&self, st: &libc::stat) -> bool {
+        // Only named objects can lose their name, memfds do not even have a link
+        if !matches!(self.backend, Backend::Shm | Backend::File) {
+            return false;
+        }
+        if st.st_nlink == 0 {
+            return true;
+        }
+        let current = match self.backend {
+            Backend::Shm => shm_open(self.unique_id.as_str(), OFlag::O_RDONLY, Mode::empty())
+                .and_then(|fd| fstat(fd.as_raw_fd())),
+            _ => nix::sys::stat::stat(self.unique_id.as_str()),
+        };
+        match current {
+            Ok(cur) => cur.st_dev != st.st_dev || cur.st_ino != st.st_ino,
+            Err(nix::Error::ENOENT) => true,
+            // We cannot tell
+            Err(_) => false,
+        }
+    }
+
+    /// Returns whether no other handle holds the lock taken by `lock_attached()`
+    ///
+    /// When this is the last handle, the lock becomes exclusive to keep new handles from attaching until the object
+    /// is deleted. Otherwise the kernel drops our shared lock, which only happens while the handle is being dropped.
+    pub fn is_last_attached(&self) -> Option<bool> {
+        if self.backend == Backend::Fd {
+            return None;
+        }
+        let fd = self.map_fd.as_ref()?.as_raw_fd();
+        let res = unsafe { libc::flock(fd, libc::LOCK_EX | libc::LOCK_NB) };
+        trace!("flock({}, LOCK_EX | LOCK_NB) == {}", fd, res);
+        match res {
+            0 => Some(true),
+            _ if nix::Error::last() == nix::Error::EWOULDBLOCK => Some(false),
+            _ => None,
+        }
+    }
+
+    /// Identity of the backing object, objects created under the same os_id get different ones
+    ///
+    /// Regular files may reuse the inode of a deleted one, shared memory objects do not.
+    pub fn object_id(&self) -> Option<u64> {
+        let st = fstat(self.map_fd.as_ref()?.as_raw_fd()).ok()?;
+        Some((st.st_ino as u64) ^ (st.st_dev as u64).rotate_left(32))
+    }
+
+    /// Address to record in the header for openers and whether they must use it
+    pub fn recorded_base(&self) -> Option<(usize, bool)> {
+        let fixed = matches!(self.placement?, Placement::Fixed(_));
+        Some((self.map_ptr.as_ptr() as usize, fixed))
+    }
+
+    /// Maps the object again at the address recorded by its creator
+    ///
+    /// Placements requested by the opener win, windows and ring buffers do not have the creator's layout.
+    pub fn relocate(
+        &mut self,
+        base: usize,
+        fixed: bool,
+        ext: &ShmemConfExt,
+    ) -> Result<(), ShmemError> {
+        if ext.placement.is_some() || self.window_offset.is_some() || self.ring_buffer {
+            return Ok(());
+        }
+        let recorded = if fixed {
+            Placement::Fixed(base)
+        } else {
+            Placement::Hint(base)
+        };
+        self.placement = Some(recorded);
+        if base == self.map_ptr.as_ptr() as usize {
+            return Ok(());
+        }
+
+        debug!("Moving mapping to its recorded base address {:#x}", base);
+        let map_fd = match self.map_fd {
+            Some(ref v) => v,
+            None => return Err(ShmemError::MapOpenFailed(libc::EINVAL as _)),
+        };
+        let len = NonZeroUsize::new(self.mapped_len()).ok_or(ShmemError::MapSizeZero)?;
+        let layout = self.layout();
+        // Unmap first in case both ranges overlap
+        let (old_ptr, old_len) = self.reserved_range();
+        trace!("munmap(map_ptr:{:p},map_size:{})", old_ptr, old_len);
+        let _ = unsafe { munmap(old_ptr, old_len) };
+        // Nothing is left to unmap if the recorded address is taken
+        self.map_ptr = NonNull::dangling();
+        self.guard_len = 0;
+        self.map_ptr = match map_object(
+            Some(recorded),
+            len,
+            self.map_prot,
+            MapFlags::MAP_SHARED | ext.map_flags(),
+            Some(map_fd.as_fd()),
+            0,
+            layout,
+        ) {
+            Ok(v) => v,
+            Err(e) => return Err(map_open_error(e, Some(recorded))),
+        };
+        self.guard_len = layout.guard_len;
+
+        // Locks and NUMA policies do not carry over to the new mapping
+        self.options = MapOptions::default();
+        apply_map_options(self, ext)
+    }
+
+    /// Sets the size of the backing object and remaps it
+    pub fn resize(&mut self, new_size: usize) -> Result<(), ShmemError> {
+        if new_size == 0 {
+            return Err(ShmemError::MapSizeZero);
+        }
+
+        let map_fd = match self.map_fd {
+            Some(ref v) => v,
+            // Anonymous mappings cannot change size for the other processes
+            None => return Err(ShmemError::MapResizeFailed(libc::EINVAL as _)),
+        };
+        // Mappings placed at a chosen address must not move, windows and ring buffers have a fixed size
+        if self.placement.is_some() || self.window_offset.is_some() || self.ring_buffer {
+            return Err(ShmemError::MapResizeFailed(libc::EINVAL as _));
+        }
+        // Huge page backed objects can only hold whole pages
+        let new_size = if self.page_size > page_size() {
+            new_size.next_multiple_of(self.page_size)
+        } else {
+            new_size
+        };
+
+        let old_size = match fstat(map_fd.as_raw_fd()) {
+            Ok(v) => v.st_size,
+            Err(e) => return Err(ShmemError::MapResizeFailed(e as u32)),
+        };
+        trace!("ftruncate({:?}, {})", map_fd, new_size);
+        if let Err(e) = ftruncate(map_fd, new_size as _) {
+            return Err(ShmemError::MapResizeFailed(e as u32));
+        }
+
+        let res = self.remap(new_size);
+        // Keep the object the size of our mapping if we could not remap it
+        if res.is_err() && self.map_size != new_size {
+            if let Some(ref map_fd) = self.map_fd {
+                trace!("ftruncate({:?}, {})", map_fd, old_size);
+                let _ = ftruncate(map_fd, old_size);
+            }
+        }
+        res
+    }
+
+    /// Returns the address of a page aligned range of the mapping
+    fn page_range(&self, offset: usize, len: usize) -> Result<NonNull<c_void>, ShmemError> {
+        if offset
+            .checked_add(len)
+            .is_none_or(|end| end > self.map_size)
+        {
+            return Err(ShmemError::RangeOutOfBounds { offset, len });
+        }
+        let addr = unsafe { self.map_ptr.byte_add(self.data_offset + offset) };
+        if !(addr.as_ptr() as usize).is_multiple_of(self.page_size) {
+            return Err(ShmemError::RangeNotAligned { offset, len });
+        }
+        Ok(addr)
+    }
+
+    /// Maps the same range again with another protection
+    ///
+    /// Private read-only mappings do not count as writable mappings when sealing a memfd, they see the same content
+    /// as long as nobody can write to it.
+    fn remap_in_place(&mut self, prot: ProtFlags, private: bool) -> nix::Result<()> {
+        let map_fd = self.map_fd.as_ref().ok_or(nix::Error::EINVAL)?;
+        let copies = if self.ring_buffer { 2 } else { 1 };
+        let len = NonZeroUsize::new(self.mapped_len() / copies).ok_or(nix::Error::EINVAL)?;
+        let offset = self.window_offset.map_or(0, |o| o - self.data_offset);
+        let flags = MapFlags::MAP_FIXED
+            | if private {
+                MapFlags::MAP_PRIVATE
+            } else {
+                MapFlags::MAP_SHARED
+            };
+
+        // Replace our mapping in place so pointers into it stay valid
+        for i in 0..copies {
+            let addr = unsafe { self.map_ptr.byte_add(i * len.get()) };
+            let res = unsafe {
+                mmap(
+                    NonZeroUsize::new(addr.as_ptr() as usize),
+                    len,
+                    prot,
+                    flags,
+                    map_fd,
+                    offset as _,
+                )
+            };
+            trace!(
+                "mmap({:p}, {}, {:X}, {:X}, {:?}, {}) == {:?}",
+                addr,
+                len,
+                prot,
+                flags,
+                map_fd,
+                offset,
+                res
+            );
+            res?;
+        }
+        self.map_prot = prot;
+
+        if self.options.locked {
+            unsafe { mlock(self.map_ptr, self.mapped_len()) }?;
+        }
+        Ok(())
+    }
+
+    /// Seals the backing object, with memfd seals when possible
+    fn seal(&mut self, seals: Seals) -> Result<(), ShmemError> {
+        let map_fd = match self.map_fd {
+            Some(ref v) => v.as_fd(),
+            None => return Err(ShmemError::MapSealFailed(libc::EINVAL as _)),
+        };
+
+        #[cfg(target_os = "linux")]
+        {
+            // Objects that do not support seals report F_SEAL_SEAL or an error
+            let cur = unsafe { libc::fcntl(map_fd.as_raw_fd(), libc::F_GET_SEALS) };
+            trace!("fcntl({:?}, F_GET_SEALS) == {:X}", map_fd, cur);
+            if cur >= 0 && cur & libc::F_SEAL_SEAL == 0 {
+                let mut flags = 0;
+                if seals.no_shrink {
+                    flags |= libc::F_SEAL_SHRINK;
+                }
+                if seals.no_grow {
+                    flags |= libc::F_SEAL_GROW;
+                }
+                let prev_prot = self.map_prot;
+                if seals.no_write {
+                    flags |= libc::F_SEAL_WRITE;
+                    // The write seal is refused as long as writable mappings exist, ours included
+                    if let Err(e) = self.remap_in_place(ProtFlags::PROT_READ, true) {
+                        return Err(ShmemError::MapSealFailed(e as u32));
+                    }
+                }
+
+                let map_fd = self.map_fd.as_ref().unwrap();
+                let res = unsafe { libc::fcntl(map_fd.as_raw_fd(), libc::F_ADD_SEALS, flags) };
+                trace!("fcntl({:?}, F_ADD_SEALS, {:X}) == {}", map_fd, flags, res);
+                if res != 0 {
+                    let err = nix::Error::last();
+                    // Give our shared mapping back, writes would fault otherwise
+                    if seals.no_write {
+                        if let Err(_e) = self.remap_in_place(prev_prot, false) {
+                            debug!(
+                                "Failed to restore the mapping after sealing failed : {}",
+                                _e
+                            );
+                        }
+                    }
+                    return Err(ShmemError::MapSealFailed(err as u32));
+                }
+                return Ok(());
+            }
+        }
+
+        // Only writes can be prevented without kernel seals, refuse rather than drop the other seals
+        if !seals.no_write || seals.no_shrink || seals.no_grow {
+            return Err(ShmemError::MapSealFailed(libc::EOPNOTSUPP as _));
+        }
+
+        // Prevent anyone from opening the object for writing (or resizing) it, then stop writing ourselves
+        debug!("Falling back to removing write permissions to seal the mapping");
+        let prev_mode = match fstat(map_fd.as_raw_fd()) {
+            Ok(v) => Mode::from_bits_truncate(v.st_mode as _),
+            Err(e) => return Err(ShmemError::MapSealFailed(e as u32)),
+        };
+        let mode = prev_mode - (Mode::S_IWUSR | Mode::S_IWGRP | Mode::S_IWOTH);
+        trace!("fchmod({:?}, {:o})", map_fd, mode);
+        if let Err(e) = fchmod(map_fd.as_raw_fd(), mode) {
+            return Err(ShmemError::MapSealFailed(e as u32));
+        }
+        if let Err(e) = self.remap_in_place(ProtFlags::PROT_READ, false) {
+            // Nothing was sealed, let others write again
+            let map_fd = self.map_fd.as_ref().unwrap();
+            trace!("fchmod({:?}, {:o})", map_fd, prev_mode);
+            if let Err(_e) = fchmod(map_fd.as_raw_fd(), prev_mode) {
+                debug!(
+                    "Failed to restore permissions after sealing failed : {}",
+                    _e
+                );
+            }
+            return Err(ShmemError::MapSealFailed(e as u32));
+        }
+        Ok(())
+    }
+
+    /// Remaps the backing object if its size was changed by someone else
+    pub fn refresh(&mut self) -> Result<bool, ShmemError> {
+        let map_fd = match self.map_fd {
+            Some(ref v) if self.window_offset.is_none() && !self.ring_buffer => v,
+            _ => return Ok(false),
+        };
+
+        let cur_size = match fstat(map_fd.as_raw_fd()) {
+            Ok(v) => v.st_size as usize,
+            Err(e) => return Err(ShmemError::MapResizeFailed(e as u32)),
+        };
+
+        if cur_size == self.mapped_len() {
+            return Ok(false);
+        }
+        if cur_size == 0 {
+            return Err(ShmemError::MapSizeZero);
+        }
+        if self.placement.is_some() {
+            return Err(ShmemError::MapResizeFailed(libc::EINVAL as _));
+        }
+
+        debug!(
+            "Mapping '{}' changed size from {} to {}",
+            self.unique_id, self.map_size, cur_size
+        );
+        self.remap(cur_size)?;
+        Ok(true)
+    }
+
+    #[cfg(target_os = "linux")]
+    fn remap(&mut self, new_size: usize) -> Result<(), ShmemError> {
+        // mremap() would leave the guard pages behind
+        if self.guard_len != 0 {
+            return self.remap_moved(new_size);
+        }
+
+        self.map_ptr = match unsafe {
+            mremap(
+                self.map_ptr,
+                self.map_size,
+                new_size,
+                MRemapFlags::MREMAP_MAYMOVE,
+                None,
+            )
+        } {
+            Ok(v) => {
+                trace!(
+                    "mremap({:p}, {}, {}, MREMAP_MAYMOVE) == {:p}",
+                    self.map_ptr,
+                    self.map_size,
+                    new_size,
+                    v
+                );
+                v
+            }
+            Err(e) => return Err(ShmemError::MapResizeFailed(e as u32)),
+        };
+        self.map_size = new_size;
+        Ok(())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    fn remap(&mut self, new_size: usize) -> Result<(), ShmemError> {
+        self.remap_moved(new_size)
+    }
+
+    /// Maps the new size somewhere else and releases the old mapping
+    fn remap_moved(&mut self, new_size: usize) -> Result<(), ShmemError> {
+        let nz_map_size = NonZeroUsize::new(new_size).ok_or(ShmemError::MapSizeZero)?;
+        let map_fd = match self.map_fd {
+            Some(ref v) => v,
+            None => return Err(ShmemError::MapResizeFailed(libc::EINVAL as _)),
+        };
+
+        let new_ptr = match map_object(
+            None,
+            nz_map_size,
+            self.map_prot,
+            MapFlags::MAP_SHARED,
+            Some(map_fd.as_fd()),
+            0,
+            self.layout(),
+        ) {
+            Ok(v) => v,
+            Err(e) => return Err(ShmemError::MapResizeFailed(e as u32)),
+        };
+
+        let (reserved_ptr, reserved_len) = self.reserved_range();
+        trace!(
+            "munmap(map_ptr:{:p},map_size:{})",
+            reserved_ptr,
+            reserved_len
+        );
+        if let Err(_e) = unsafe { munmap(reserved_ptr, reserved_len) } {
+            debug!("Failed to munmap() shared memory mapping : {}", _e);
+        };
+
+        self.map_ptr = new_ptr;
+        self.map_size = new_size;
+
+        // Locks do not carry over to the new mapping
+        if self.options.locked {
+            trace!("mlock({:p}, {})", self.map_ptr, self.map_size);
+            if let Err(e) = unsafe { mlock(self.map_ptr, self.map_size) } {
+                self.options.locked = false;
+                return Err(lock_error(e, self.map_size));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ShmemConf {
+    /// Maps a regular file at `path` instead of a shared memory object
+    ///
+    /// `create()` creates the file or opens it if it already exists (keeping its content) and grows it
+    /// to the requested size. `open()` only opens an existing file. The path becomes the mapping's os_id.
+    ///
+    /// The file is not deleted when the mapping is dropped unless ownership is taken with `Shmem::set_owner(true)`,
+    /// `Shmem::is_owner()` returns false until then. The same goes for the flink.
+    /// Use `Shmem::flush()` to make sure modifications are written to disk.
+    pub fn backing_file<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.os_id = Some(path.as_ref().to_string_lossy().into_owned());
+        self.ext.backend = Backend::File;
+        self
+    }
+
+    /// Sets the permission bits of created mappings and backing files
+    ///
+    /// Defaults to `0o666`. Like for any file, the umask is applied on top of these bits unless
+    /// `ignore_umask()` is used. Backing files that already exist keep their permissions and group.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.ext.mode = Some(mode);
+        self
+    }
+
+    /// Sets the permission bits of created flinks
+    ///
+    /// Defaults to `0o666`, filtered by the umask unless `ignore_umask()` is used.
+    pub fn flink_mode(mut self, mode: u32) -> Self {
+        self.ext.flink_mode = Some(mode);
+        self
+    }
+
+    /// Makes `open()` refuse flinks that are not owned by `uid`
+    ///
+    /// Flinks are never followed through symbolic links, checking their owner makes it safe to keep them in
+    /// directories other users can write to.
+    pub fn flink_owner(mut self, uid: u32) -> Self {
+        self.ext.flink_owner = Some(uid);
+        self
+    }
+
+    /// Sets the group owning created mappings and flinks
+    pub fn group(mut self, gid: u32) -> Self {
+        self.ext.group = Some(gid);
+        self
+    }
+
+    /// Applies `mode()` and `flink_mode()` as is, without filtering them through the umask
+    pub fn ignore_umask(mut self) -> Self {
+        self.ext.ignore_umask = true;
+        self
+    }
+
+    /// Backs the mapping with huge pages of the given size
+    ///
+    /// The requested size is rounded up to a multiple of the page size. Huge pages are available
+    /// for `memfd()` and `anonymous()` mappings and for `backing_file()` paths on a hugetlbfs mount,
+    /// `ShmemError::HugePagesUnavailable` is returned when the system cannot provide them.
+    #[cfg(target_os = "linux")]
+    pub fn huge_pages(mut self, page_size: HugePageSize) -> Self {
+        self.ext.huge_pages = Some(page_size);
+        self
+    }
+
+    /// Only maps `len` bytes starting at `offset` of the shared memory when opening it
+    ///
+    /// `offset` does not need to be page aligned, `Shmem::as_ptr()` points at `offset` and `Shmem::len()` is `len`.
+    /// `create()` fails with `MapCreateFailed(EINVAL)` when a window is configured, create the whole shared memory
+    /// first.
+    /// Windows cannot be resized and cannot be combined with `header()` or `cleanup(CleanupPolicy::RefCount)`. See
+    /// `Shmem::map_window()` to map more windows of the same shared memory.
+    pub fn window(mut self, offset: usize, len: usize) -> Self {
+        self.ext.window = Some((offset, len));
+        self
+    }
+
+    /// Maps the shared memory twice back to back to use it as a ring buffer
+    ///
+    /// `Shmem::as_ptr()` points to `2 * len()` bytes where `ptr[i]` and `ptr[i + len()]` are the same byte, so reads
+    /// and writes that go past the end of the buffer wrap around without special code. `create()` rounds the size up
+    /// to a multiple of the page size, `open()` requires the shared memory (or the `window()`) to be page aligned.
+    /// Ring buffers cannot be resized, do not record their address for `fixed_address()` and cannot be combined with
+    /// `header()` or `cleanup(CleanupPolicy::RefCount)`.
+    pub fn ring_buffer(mut self) -> Self {
+        self.ext.ring_buffer = true;
+        self
+    }
+
+    /// Makes `open()` fail with `ShmemError::NotSealed` unless the shared memory has at least these seals
+    ///
+    /// See `Shmem::seal()`. Shared memory sealed against writes has to be opened with `open_read_only()`.
+    /// `Seals::ALL` only accepts seals enforced by the kernel, require `Seals::write_protected` to accept the
+    /// weaker fallback.
+    pub fn require_seals(mut self, seals: Seals) -> Self {
+        self.ext.required_seals = seals;
+        self
+    }
+
+    /// Surrounds the mapping with inaccessible guard pages
+    ///
+    /// Reading or writing right before or after the mapping raises `SIGSEGV` instead of silently touching
+    /// whatever else is mapped there. `Shmem::as_ptr()` and `Shmem::len()` only cover the usable memory,
+    /// the guard pages do not use any memory.
+    pub fn guard_pages(mut self) -> Self {
+        self.ext.guard_pages = true;
+        self
+    }
+
+    /// Asks for the mapping to be placed at `addr` in the address space
+    ///
+    /// The mapping is placed elsewhere if the range is already in use. When a header is configured with
+    /// `ShmemConf::header()`, the address chosen by `create()` is recorded in it and openers configured with the same
+    /// header try to map it at the same address. Without a header, only the creator is placed. The mapping cannot be
+    /// resized.
+    pub fn address_hint(mut self, addr: usize) -> Self {
+        self.ext.placement = Some(Placement::Hint(addr));
+        self
+    }
+
+    /// Requires the mapping to be placed at `addr` in the address space
+    ///
+    /// This is what makes it possible to store pointers inside the shared memory, the mapping starts at `addr` in
+    /// every process. `addr` must be page aligned, `ShmemError::AddressInUse` is returned when the range is already in
+    /// use. Like with `address_hint()`, the address is only recorded when a header is configured, `Shmem::as_ptr()`
+    /// then points right after it. `open()` maps it at the same address when configured with the same header, failing
+    /// with `ShmemError::AddressInUse` if that is not possible.
+    ///
+    /// Uses `MAP_FIXED_NOREPLACE` on Linux, existing mappings are never replaced.
+    pub fn fixed_address(mut self, addr: usize) -> Self {
+        self.ext.placement = Some(Placement::Fixed(addr));
+        self
+    }
+
+    /// Prefaults the whole mapping with `MAP_POPULATE` when creating or opening it
+    ///
+    /// This avoids page faults the first time the memory is accessed. Whether every page ended up
+    /// resident is reported by `Shmem::map_options()`.
+    #[cfg(target_os = "linux")]
+    pub fn populate(mut self) -> Self {
+        self.ext.populate = true;
+        self
+    }
+
+    /// Maps the shared memory with `MAP_NORESERVE` so no swap space is reserved for it
+    ///
+    /// Only `anonymous()` mappings reserve swap space when they are created, the memory of other objects is charged
+    /// as their pages are allocated whatever the flags. The kernel also ignores this flag when overcommit is disabled
+    /// (`vm.overcommit_memory = 2`). `Shmem::map_options()` reports whether it had an effect.
+    #[cfg(target_os = "linux")]
+    pub fn no_reserve(mut self) -> Self {
+        self.ext.no_reserve = true;
+        self
+    }
+
+    /// Applies a NUMA memory policy to the mapping when creating or opening it
+    ///
+    /// The policy of shared memory is shared by every process mapping it. See `Shmem::set_numa_policy()`
+    /// to change it afterwards.
+    #[cfg(target_os = "linux")]
+    pub fn numa_policy(mut self, policy: NumaPolicy) -> Self {
+        self.ext.numa_policy = Some(policy);
+        self
+    }
+
+    /// Locks the whole mapping in memory with `mlock()` when creating or opening it
+    ///
+    /// Locked pages are resident and never swapped out. `ShmemError::MemlockLimitExceeded` is returned when
+    /// the mapping is larger than what `RLIMIT_MEMLOCK` allows this process to lock.
+    pub fn lock(mut self) -> Self {
+        self.ext.lock = true;
+        self
+    }
+
+    /// Creates an anonymous shared mapping that has no name and no backing object to clean up
+    ///
+    /// The mapping can only be shared with child processes through `fork()`, each child inherits a
+    /// usable copy of the `Shmem`. The memory is released once the last process unmaps it.
+    pub fn anonymous(mut self) -> Self {
+        self.ext.backend = Backend::Anonymous;
+        self
+    }
+
+    /// Opens the mapping backed by an existing file descriptor
+    ///
+    /// This is how processes get access to mappings that have no name (see `memfd()` and `Shmem::fd()`),
+    /// after inheriting the descriptor or receiving it over a unix socket. The descriptor is duplicated, the caller keeps
+    /// ownership of `fd`.
+    pub fn open_fd(self, fd: BorrowedFd<'_>) -> Result<Shmem, ShmemError> {
+        self.open_fd_inner(fd, false)
+    }
+
+    /// Opens the mapping backed by an existing file descriptor in read-only mode
+    ///
+    /// See `open_fd()` and `open_read_only()`
+    pub fn open_fd_read_only(self, fd: BorrowedFd<'_>) -> Result<ReadOnlyShmem, ShmemError> {
+        Ok(ReadOnlyShmem {
+            inner: self.open_fd_inner(fd, true)?,
+        })
+    }
+
+    fn open_fd_inner(mut self, fd: BorrowedFd<'_>, read_only: bool) -> Result<Shmem, ShmemError> {
+        if self.header.is_some() && !self.ext.supports_header() {
+            return Err(ShmemError::HeaderNotSupported);
+        }
+        let unique_id = self
+            .os_id
+            .clone()
+            .unwrap_or_else(|| format!("fd:{}", fd.as_raw_fd()));
+        let mapping = open_fd(&unique_id, fd, read_only, &self.ext)?;
+
+        self.size = mapping.map_size;
+        self.owner = false;
+        self.read_only = read_only;
+
+        let mut shmem = Shmem {
+            config: self,
+            mapping,
+        };
+        shmem.validate_header()?;
+        shmem.map_at_recorded_base()?;
+        shmem.attach()?;
+        Ok(shmem)
+    }
+}
+
+impl Shmem {
+    /// Returns the file descriptor of the object backing the mapping
+    ///
+    /// Anonymous mappings have no file descriptor
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        self.mapping.map_fd.as_ref().map(|fd| fd.as_fd())
+    }
+
+    /// Grows or shrinks the shared memory to `new_len` bytes and remaps it
+    ///
+    /// The mapping may move, pointers previously returned by `as_ptr()` must not be used after this call.
+    ///
+    /// Other processes keep their current view until they call `refresh()`. Accessing memory past the end
+    /// of a shrunk object raises `SIGBUS`.
+    pub fn resize(&mut self, new_len: usize) -> Result<(), ShmemError> {
+        self.mapping.resize(new_len + self.header_len())?;
+        self.config.size = self.mapping.map_size;
+        self.update_header_payload_len();
+        Ok(())
+    }
+
+    /// Returns the size of the pages backing the mapping
+    ///
+    /// This reports the huge page size when the mapping is backed by huge pages
+    pub fn page_size(&self) -> usize {
+        self.mapping.page_size
+    }
+
+    /// Returns the offset of the mapped data in the shared memory, non zero when opened with `ShmemConf::window()`
+    pub fn offset(&self) -> usize {
+        self.mapping.window_offset.unwrap_or(0)
+    }
+
+    /// Maps another `len` bytes starting at `offset` of the shared memory
+    ///
+    /// `offset` is relative to the start of the shared memory, not to this mapping. The window is independent from
+    /// this handle and stays valid after it is dropped. Anonymous mappings cannot be mapped again.
+    pub fn map_window(&self, offset: usize, len: usize) -> Result<ShmemWindow, ShmemError> {
+        let map_fd = match self.mapping.map_fd {
+            Some(ref v) => v,
+            None => return Err(ShmemError::MapOpenFailed(libc::EINVAL as _)),
+        };
+        let size = match fstat(map_fd.as_raw_fd()) {
+            Ok(v) => v.st_size as usize,
+            Err(e) => return Err(ShmemError::MapOpenFailed(e as u32)),
+        };
+        if offset.checked_add(len).is_none_or(|end| end > size) {
+            return Err(ShmemError::RangeOutOfBounds { offset, len });
+        }
+
+        let map_offset = offset - (offset % self.mapping.page_size);
+        let map_len =
+            NonZeroUsize::new(len + (offset - map_offset)).ok_or(ShmemError::MapSizeZero)?;
+        let map_ptr = match map_placed(
+            None,
+            map_len,
+            self.mapping.map_prot,
+            MapFlags::MAP_SHARED,
+            Some(map_fd.as_fd()),
+            map_offset,
+        ) {
+            Ok(v) => v,
+            Err(e) => return Err(ShmemError::MapOpenFailed(e as u32)),
+        };
+
+        Ok(ShmemWindow {
+            map_ptr,
+            map_len: map_len.get(),
+            data_offset: offset - map_offset,
+            offset,
+            len,
+        })
+    }
+
+    /// Seals the shared memory so that nobody can change its size or content anymore
+    ///
+    /// Seals are permanent. `memfd()` mappings are sealed by the kernel with `F_ADD_SEALS`. Sealing them against
+    /// writes fails with `EBUSY` while other processes have them mapped for writing, and this mapping is remapped
+    /// read-only beforehand (and back if sealing fails). Other objects can only be sealed against writes, they fall
+    /// back to removing their write permissions and remapping this mapping read-only. This is reported as
+    /// `Seals::write_protected` by `seals()`, see its documentation for what it does not prevent. Asking them for
+    /// `no_shrink` or `no_grow`, `Seals::ALL` included, fails with `EOPNOTSUPP` and changes nothing. Mappings stay at
+    /// the same address.
+    pub fn seal(&mut self, seals: Seals) -> Result<(), ShmemError> {
+        if seals.is_empty() {
+            return Ok(());
+        }
+        self.mapping.seal(seals)
+    }
+
+    /// Returns the seals applied to the shared memory
+    pub fn seals(&self) -> Result<Seals, ShmemError> {
+        match self.mapping.map_fd {
+            Some(ref v) => fd_seals(v.as_fd()).map_err(|e| ShmemError::UnknownOsError(e as u32)),
+            None => Ok(Seals::default()),
+        }
+    }
+
+    /// Returns which of the requested `populate()`, `lock()` and `no_reserve()` options took effect
+    pub fn map_options(&self) -> MapOptions {
+        self.mapping.options
+    }
+
+    /// Sets the NUMA memory policy of the mapping with `mbind()`
+    ///
+    /// Pages that are already allocated and only mapped by this process are migrated to match the new policy.
+    #[cfg(target_os = "linux")]
+    pub fn set_numa_policy(&self, policy: &NumaPolicy) -> Result<(), ShmemError> {
+        self.mapping.mbind(policy)
+    }
+
+    /// Returns the NUMA memory policy of the mapping with `get_mempolicy()`
+    #[cfg(target_os = "linux")]
+    pub fn numa_policy(&self) -> Result<NumaPolicy, ShmemError> {
+        let mut mode: libc::c_int = 0;
+        let mut mask = vec![0; NumaPolicy::MAX_NODES / NumaPolicy::MASK_BITS];
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_get_mempolicy,
+                &mut mode as *mut libc::c_int,
+                mask.as_mut_ptr(),
+                NumaPolicy::MAX_NODES + 1,
+                self.mapping.map_ptr.as_ptr(),
+                MPOL_F_ADDR,
+            )
+        };
+        trace!(
+            "get_mempolicy({}, {:?}, {}, {:p}, MPOL_F_ADDR) == {}",
+            mode,
+            mask,
+            NumaPolicy::MAX_NODES + 1,
+            self.mapping.map_ptr,
+            res
+        );
+        if res != 0 {
+            return Err(ShmemError::NumaQueryFailed(nix::Error::last() as u32));
+        }
+        Ok(NumaPolicy::from_raw(mode, &mask))
+    }
+
+    /// Returns the NUMA node of every page of the mapping with `move_pages()`
+    ///
+    /// Pages that are not resident in memory are reported as `None`.
+    #[cfg(target_os = "linux")]
+    pub fn numa_nodes(&self) -> Result<Vec<Option<u32>>, ShmemError> {
+        let page_size = self.mapping.page_size;
+        let pages: Vec<*mut c_void> = (0..self.mapping.mapped_len().div_ceil(page_size))
+            .map(|i| unsafe { self.mapping.map_ptr.as_ptr().byte_add(i * page_size) })
+            .collect();
+        let mut status: Vec<libc::c_int> = vec![0; pages.len()];
+
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_move_pages,
+                0,
+                pages.len(),
+                pages.as_ptr(),
+                std::ptr::null::<libc::c_int>(),
+                status.as_mut_ptr(),
+                0,
+            )
+        };
+        trace!("move_pages(0, {}, NULL, 0) == {}", pages.len(), res);
+        if res != 0 {
+            return Err(ShmemError::NumaQueryFailed(nix::Error::last() as u32));
+        }
+
+        // Negative values are errors like -ENOENT for pages that are not present
+        Ok(status
+            .into_iter()
+            .map(|s| if s < 0 { None } else { Some(s as u32) })
+            .collect())
+    }
+
+    /// Synchronously writes the whole mapping back to its backing object
+    pub fn flush(&self) -> Result<(), ShmemError> {
+        self.flush_range(0, self.len())
+    }
+
+    /// Synchronously writes `len` bytes starting at `offset` back to the backing object
+    pub fn flush_range(&self, offset: usize, len: usize) -> Result<(), ShmemError> {
+        if offset.checked_add(len).is_none_or(|end| end > self.len()) {
+            return Err(ShmemError::RangeOutOfBounds { offset, len });
+        }
+        if len == 0 {
+            return Ok(());
+        }
+
+        // msync() requires a page aligned address
+        let offset = self.mapping.data_offset + self.header_len() + offset;
+        let start = offset - (offset % self.mapping.page_size);
+        let len = len + (offset - start);
+        let addr = unsafe { self.mapping.map_ptr.byte_add(start) };
+
+        trace!("msync({:p}, {}, MS_SYNC)", addr, len);
+        if let Err(e) = unsafe { msync(addr, len, MsFlags::MS_SYNC) } {
+            return Err(ShmemError::MapFlushFailed(e as u32));
+        }
+        Ok(())
+    }
+
+    /// Returns the address of a page aligned range of the payload, errors report the range relative to the payload
+    fn payload_range(&self, offset: usize, len: usize) -> Result<NonNull<c_void>, ShmemError> {
+        let res = match offset.checked_add(self.header_len()) {
+            Some(start) => self.mapping.page_range(start, len),
+            None => return Err(ShmemError::RangeOutOfBounds { offset, len }),
+        };
+        res.map_err(|e| match e {
+            ShmemError::RangeOutOfBounds { .. } => ShmemError::RangeOutOfBounds { offset, len },
+            ShmemError::RangeNotAligned { .. } => ShmemError::RangeNotAligned { offset, len },
+            e => e,
+        })
+    }
+
+    /// Changes the access allowed to `len` bytes starting at `offset`
+    ///
+    /// The range must start on a page boundary, where `as_ptr() + offset` is a multiple of `page_size()`, and is
+    /// extended to the end of its last page. `offset` is a multiple of `page_size()` unless only a window is mapped or
+    /// a header is configured with `ShmemConf::header()`, the payload then starts 128 bytes into its first page.
+    /// Only this process' view of the memory is affected. Accessing the range in a way it does not allow
+    /// raises `SIGSEGV`, which makes this a good way to catch stray writes.
+    pub fn protect(
+        &self,
+        offset: usize,
+        len: usize,
+        protection: Protection,
+    ) -> Result<(), ShmemError> {
+        let addr = self.payload_range(offset, len)?;
+        trace!(
+            "mprotect({:p}, {}, {:?})",
+            addr,
+            len,
+            protection.prot_flags()
+        );
+        if let Err(e) = unsafe { mprotect(addr, len, protection.prot_flags()) } {
+            return Err(ShmemError::MapProtectFailed(e as u32));
+        }
+        Ok(())
+    }
+
+    /// Tells the kernel how `len` bytes starting at `offset` are going to be used
+    ///
+    /// The range must start on a page boundary. Dropping pages with `Advice::DontNeed` does not
+    /// discard their content, it is read back from the shared memory on the next access.
+    pub fn advise(&self, offset: usize, len: usize, advice: Advice) -> Result<(), ShmemError> {
+        let addr = self.payload_range(offset, len)?;
+        trace!("madvise({:p}, {}, {:?})", addr, len, advice.madvise_flag());
+        if let Err(e) = unsafe { madvise(addr, len, advice.madvise_flag()) } {
+            return Err(ShmemError::MapAdviseFailed(e as u32));
+        }
+        Ok(())
+    }
+
+    /// Locks `len` bytes starting at `offset` in memory
+    ///
+    /// The range must start on a page boundary. See `ShmemConf::lock()` to lock the whole mapping.
+    pub fn lock_range(&self, offset: usize, len: usize) -> Result<(), ShmemError> {
+        let addr = self.payload_range(offset, len)?;
+        trace!("mlock({:p}, {})", addr, len);
+        if let Err(e) = unsafe { mlock(addr, len) } {
+            return Err(lock_error(e, len));
+        }
+        Ok(())
+    }
+
+    /// Unlocks `len` bytes starting at `offset` so they can be swapped out again
+    ///
+    /// The range must start on a page boundary.
+    pub fn unlock_range(&self, offset: usize, len: usize) -> Result<(), ShmemError> {
+        let addr = self.payload_range(offset, len)?;
+        trace!("munlock({:p}, {})", addr, len);
+        if let Err(e) = unsafe { munlock(addr, len) } {
+            return Err(ShmemError::MapLockFailed(e as u32));
+        }
+        Ok(())
+    }
+
+    /// Returns the status of the object backing the mapping
+    ///
+    /// `ShmemStat::unlinked` tells whether the os_id was deleted or now leads to another object, in which case this
+    /// handle is attached to shared memory that new processes cannot find. Mappings without a name, like memfd or
+    /// file descriptor ones, are never reported as unlinked. Anonymous mappings have no object to describe.
+    #[allow(clippy::unnecessary_cast)]
+    pub fn stat(&self) -> Result<ShmemStat, ShmemError> {
+        let fd = match self.mapping.map_fd.as_ref() {
+            Some(fd) => fd,
+            None => return Err(ShmemError::MapStatFailed(libc::EBADF as u32)),
+        };
+        let st = match fstat(fd.as_raw_fd()) {
+            Ok(v) => v,
+            Err(e) => return Err(ShmemError::MapStatFailed(e as u32)),
+        };
+        trace!("fstat({:?}) == {:?}", fd, st);
+
+        #[cfg(target_os = "linux")]
+        let created = birth_time(fd.as_fd());
+        #[cfg(any(target_os = "freebsd", target_os = "macos"))]
+        let created = Some(system_time(
+            st.st_birthtime as i64,
+            st.st_birthtime_nsec as i64,
+        ));
+
+        Ok(ShmemStat {
+            uid: st.st_uid,
+            gid: st.st_gid,
+            mode: st.st_mode as u32 & 0o7777,
+            nlink: st.st_nlink as u64,
+            size: st.st_size as usize,
+            blocks: st.st_blocks as u64,
+            created,
+            modified: system_time(st.st_mtime as i64, st.st_mtime_nsec as i64),
+            accessed: system_time(st.st_atime as i64, st.st_atime_nsec as i64),
+            changed: system_time(st.st_ctime as i64, st.st_ctime_nsec as i64),
+            unlinked: self.mapping.is_unlinked(&st),
+        })
+    }
+
+    /// Remaps the shared memory if another process resized it
+    ///
+    /// The size of the backing object acts as the generation counter, this returns true
+    /// when the mapping was remapped (and may have moved). A shrink followed by a grow back to the same size is not
+    /// detected, which is harmless since the mapping still covers the same object and the pages that were cut off read
+    /// as zeroes like they do for the process that resized it.
+    pub fn refresh(&mut self) -> Result<bool, ShmemError> {
+        let remapped = self.mapping.refresh()?;
+        self.config.size = self.mapping.map_size;
+        Ok(remapped)
+    }
+}
+
+/// Window on a range of a shared memory mapping, created by `Shmem::map_window()`
+pub struct ShmemWindow {
+    map_ptr: NonNull<c_void>,
+    map_len: usize,
+    data_offset: usize,
+    offset: usize,
+    len: usize,
+}
+#[allow(clippy::len_without_is_empty)]
+impl ShmemWindow {
+    /// Returns the offset of the window in the shared memory
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+    /// Returns the size of the window
+    pub fn len(&self) -> usize {
+        self.len
+    }
+    /// Returns a raw pointer to the start of the window
+    pub fn as_ptr(&self) -> *mut u8 {
+        unsafe { self.map_ptr.byte_add(self.data_offset).as_ptr() as _ }
+    }
+    /// Returns the window as a byte slice
+    /// # Safety
+    /// This function is unsafe because it is impossible to ensure the range of bytes is immutable
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        std::slice::from_raw_parts(self.as_ptr(), self.len())
+    }
+    /// Returns the window as a mutable byte slice
+    /// # Safety
+    /// This function is unsafe because it is impossible to ensure the returned mutable refence is unique/exclusive
+    pub unsafe fn as_slice_mut(&mut self) -> &mut [u8] {
+        std::slice::from_raw_parts_mut(self.as_ptr(), self.len())
+    }
+}
+impl Drop for ShmemWindow {
+    fn drop(&mut self) {
+        trace!(
+            "munmap(map_ptr:{:p},map_size:{})",
+            self.map_ptr,
+            self.map_len
+        );
+        if let Err(_e) = unsafe { munmap(self.map_ptr, self.map_len) } {
+            debug!("Failed to munmap() shared memory window : {}", _e);
+        };
+    }
+}
+
+impl ReadOnlyShmem {
+    /// Returns the file descriptor of the object backing the mapping
+    ///
+    /// See `Shmem::fd()`
+    pub fn fd(&self) -> Option<BorrowedFd<'_>> {
+        self.inner.fd()
+    }
+
+    /// Returns the seals applied to the shared memory
+    ///
+    /// See `Shmem::seals()`
+    pub fn seals(&self) -> Result<Seals, ShmemError> {
+        self.inner.seals()
+    }
+
+    /// Returns the status of the object backing the mapping
+    ///
+    /// See `Shmem::stat()`
+    pub fn stat(&self) -> Result<ShmemStat, ShmemError> {
+        self.inner.stat()
+    }
+
+    /// Remaps the shared memory if another process resized it
+    ///
+    /// See `Shmem::refresh()`
+    pub fn refresh(&mut self) -> Result<bool, ShmemError> {
+        self.inner.refresh()
+    }
+}
+
+/// Error code reported by `MapOpenFailed` when the mapping does not exist
+pub const MAP_NOT_FOUND: u32 = libc::ENOENT as u32;
+
+/// Returns the size of a memory page
+pub(crate) fn page_size() -> usize {
+    unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+}
+
+/// Returns the size of the pages backing the object behind `fd`
+fn fd_page_size(fd: &OwnedFd) -> usize {
+    #[cfg(target_os = "linux")]
+    {
+        // Huge page backed objects live on a hugetlbfs, its block size is the page size
+        let mut st = std::mem::MaybeUninit::<libc::statfs>::uninit();
+        if unsafe { libc::fstatfs(fd.as_raw_fd(), st.as_mut_ptr()) } == 0 {
+            let st = unsafe { st.assume_init() };
+            if st.f_type == libc::HUGETLBFS_MAGIC as _ {
+                return st.f_bsize as usize;
+            }
+        }
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = fd;
+    page_size()
+}
 
-pub struct MapData {
-    //On linux, you must shm_unlink() the object created for the mapping. It wont disappear automatically.
-    owner: bool,
+/// Maps `fd` (or anonymous memory) at the requested placement
+///
+/// Fails with `EEXIST` when a fixed placement cannot be honored.
+fn map_placed(
+    placement: Option<Placement>,
+    len: NonZeroUsize,
+    prot: ProtFlags,
+    mut flags: MapFlags,
+    fd: Option<BorrowedFd<'_>>,
+    offset: usize,
+) -> nix::Result<NonNull<c_void>> {
+    #[cfg(target_os = "linux")]
+    if let Some(Placement::Fixed(_)) = placement {
+        flags |= MapFlags::MAP_FIXED_NOREPLACE;
+    }
+    let addr = placement.and_then(|p| NonZeroUsize::new(p.addr()));
 
-    //File descriptor to our open mapping
-    map_fd: OwnedFd,
+    let map_ptr = match fd {
+        Some(fd) => unsafe { mmap(addr, len, prot, flags, fd, offset as _) },
+        None => unsafe { mmap_anonymous(addr, len, prot, flags) },
+    }?;
+    trace!(
+        "mmap({:?}, {}, {:X}, {:X}, {:?}, {}) == {:p}",
+        addr,
+        len,
+        prot,
+        flags,
+        fd,
+        offset,
+        map_ptr
+    );
 
-    //Shared mapping uid
-    pub unique_id: String,
-    //Total size of the mapping
-    pub map_size: usize,
-    //Pointer to the first address of our mapping
-    pub map_ptr: NonNull<c_void>,
+    // Kernels without MAP_FIXED_NOREPLACE treat the address as a hint
+    if let Some(Placement::Fixed(addr)) = placement {
+        if map_ptr.as_ptr() as usize != addr {
+            debug!("Mapping landed at {:p} instead of {:#x}", map_ptr, addr);
+            let _ = unsafe { munmap(map_ptr, len.get()) };
+            return Err(nix::Error::EEXIST);
+        }
+    }
+    Ok(map_ptr)
 }
 
-impl MapData {
-    pub fn as_mut_ptr(&self) -> *mut u8 {
-        self.map_ptr.as_ptr() as _
+/// How a mapping is laid out in the address space
+#[derive(Clone, Copy)]
+struct Layout {
+    /// The object is mapped twice in a row, `len` must be page aligned
+    ring_buffer: bool,
+    /// Size of the PROT_NONE guards around the mapping
+    guard_len: usize,
+    /// Size of the pages backing the object, the mapping is aligned on it
+    page_size: usize,
+}
+impl Layout {
+    fn new(ext: &ShmemConfExt, page_size: usize) -> Self {
+        Layout {
+            ring_buffer: ext.ring_buffer,
+            guard_len: if ext.guard_pages { page_size } else { 0 },
+            page_size,
+        }
     }
 }
 
-/// Shared memory teardown for linux
-impl Drop for MapData {
-    ///Takes care of properly closing the SharedMem (munmap(), shmem_unlink(), close())
-    fn drop(&mut self) {
-        //Unmap memory
+/// Maps `fd` (or anonymous memory) following `layout`, returns the address of the first copy
+fn map_object(
+    placement: Option<Placement>,
+    len: NonZeroUsize,
+    prot: ProtFlags,
+    flags: MapFlags,
+    fd: Option<BorrowedFd<'_>>,
+    offset: usize,
+    layout: Layout,
+) -> nix::Result<NonNull<c_void>> {
+    if !layout.ring_buffer && layout.guard_len == 0 {
+        return map_placed(placement, len, prot, flags, fd, offset);
+    }
+
+    // Reserve the whole range first so nothing ends up between the pieces
+    let copies = if layout.ring_buffer { 2 } else { 1 };
+    let total = len.get().next_multiple_of(layout.page_size) * copies + 2 * layout.guard_len;
+    let slack = if placement.is_none() && layout.page_size > page_size() {
+        layout.page_size
+    } else {
+        0
+    };
+    let placement = placement.map(|p| match p {
+        Placement::Hint(addr) => Placement::Hint(addr.wrapping_sub(layout.guard_len)),
+        Placement::Fixed(addr) => Placement::Fixed(addr.wrapping_sub(layout.guard_len)),
+    });
+    let reserved = map_placed(
+        placement,
+        NonZeroUsize::new(total + slack).ok_or(nix::Error::EINVAL)?,
+        ProtFlags::PROT_NONE,
+        MapFlags::MAP_PRIVATE,
+        None,
+        0,
+    )?;
+
+    // Huge pages must be mapped at an address aligned on their size
+    let base = if slack != 0 {
+        let lead =
+            (reserved.as_ptr() as usize).next_multiple_of(slack) - reserved.as_ptr() as usize;
+        unsafe {
+            if lead != 0 {
+                let _ = munmap(reserved, lead);
+            }
+            let _ = munmap(reserved.byte_add(lead + total), slack - lead);
+            reserved.byte_add(lead)
+        }
+    } else {
+        reserved
+    };
+
+    let data = unsafe { base.byte_add(layout.guard_len) };
+    for i in 0..copies {
+        let addr = unsafe { data.byte_add(i * len.get()) };
+        let res = unsafe {
+            match fd {
+                Some(fd) => mmap(
+                    NonZeroUsize::new(addr.as_ptr() as usize),
+                    len,
+                    prot,
+                    flags | MapFlags::MAP_FIXED,
+                    fd,
+                    offset as _,
+                ),
+                None => mmap_anonymous(
+                    NonZeroUsize::new(addr.as_ptr() as usize),
+                    len,
+                    prot,
+                    flags | MapFlags::MAP_FIXED,
+                ),
+            }
+        };
         trace!(
-            "munmap(map_ptr:{:p},map_size:{})",
-            self.map_ptr,
-            self.map_size
+            "mmap({:p}, {}, {:X}, {:X}, {:?}, {}) == {:?}",
+            addr,
+            len,
+            prot,
+            flags | MapFlags::MAP_FIXED,
+            fd,
+            offset,
+            res
         );
-        if let Err(_e) = unsafe { munmap(self.map_ptr, self.map_size) } {
-            debug!("Failed to munmap() shared memory mapping : {}", _e);
-        };
+        if let Err(e) = res {
+            let _ = unsafe { munmap(base, total) };
+            return Err(e);
+        }
+    }
+    Ok(data)
+}
 
-        //Unlink shmem
-        if self.map_fd.as_raw_fd() != 0 {
-            //unlink shmem if we created it
-            if self.owner {
-                debug!("Deleting persistent mapping");
-                trace!("shm_unlink({})", self.unique_id.as_str());
-                if let Err(_e) = shm_unlink(self.unique_id.as_str()) {
-                    debug!("Failed to shm_unlink() shared memory : {}", _e);
+/// Converts an error from mlock()
+#[allow(clippy::unnecessary_cast)]
+fn lock_error(e: nix::Error, len: usize) -> ShmemError {
+    if e == nix::Error::ENOMEM || e == nix::Error::EAGAIN {
+        let mut limit = std::mem::MaybeUninit::<libc::rlimit>::uninit();
+        if unsafe { libc::getrlimit(libc::RLIMIT_MEMLOCK, limit.as_mut_ptr()) } == 0 {
+            let limit = unsafe { limit.assume_init() }.rlim_cur;
+            if limit != libc::RLIM_INFINITY {
+                return ShmemError::MemlockLimitExceeded {
+                    len,
+                    limit: limit as u64,
                 };
             }
+        }
+    }
+    ShmemError::MapLockFailed(e as u32)
+}
 
-            trace!("close({:?})", self.map_fd);
-            if let Err(_e) = close(self.map_fd.as_raw_fd()) {
-                debug!(
-                    "os_impl::Linux : Failed to close() shared memory file descriptor : {}",
-                    _e
-                );
-            };
+/// Applies the options that take effect once the memory is mapped and records which ones did
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn apply_map_options(map: &mut MapData, ext: &ShmemConfExt) -> Result<(), ShmemError> {
+    let len = map.mapped_len();
+
+    // Before anything faults pages in
+    #[cfg(target_os = "linux")]
+    if let Some(ref policy) = ext.numa_policy {
+        map.mbind(policy)?;
+    }
+
+    if ext.lock {
+        trace!("mlock({:p}, {})", map.map_ptr, len);
+        if let Err(e) = unsafe { mlock(map.map_ptr, len) } {
+            return Err(lock_error(e, len));
+        }
+        map.options.locked = true;
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        // MAP_POPULATE is best effort, check that every page made it in
+        if ext.populate {
+            let mut vec = vec![0u8; len.div_ceil(page_size())];
+            map.options.populated =
+                unsafe { libc::mincore(map.map_ptr.as_ptr(), len, vec.as_mut_ptr()) } == 0
+                    && vec.iter().all(|v| v & 1 != 0);
+        }
+
+        // Objects mapped from a descriptor are never charged up front, only anonymous memory is
+        if ext.no_reserve && map.backend == Backend::Anonymous {
+            map.options.no_reserve = std::fs::read_to_string("/proc/sys/vm/overcommit_memory")
+                .is_ok_and(|v| v.trim() != "2");
         }
     }
+
+    Ok(())
 }
 
-impl MapData {
-    pub fn set_owner(&mut self, is_owner: bool) -> bool {
-        let prev_val = self.owner;
-        self.owner = is_owner;
-        prev_val
+/// Converts an error from map_placed() while creating a mapping
+#[cfg_attr(not(target_os = "linux"), allow(unused_variables))]
+fn map_create_error(e: nix::Error, ext: &ShmemConfExt) -> ShmemError {
+    if let (Some(Placement::Fixed(addr)), nix::Error::EEXIST) = (ext.placement, e) {
+        return ShmemError::AddressInUse(addr);
+    }
+    #[cfg(target_os = "linux")]
+    if ext.huge_pages.is_some() && (e == nix::Error::ENOMEM || e == nix::Error::EINVAL) {
+        return ShmemError::HugePagesUnavailable(e as u32);
+    }
+    ShmemError::MapCreateFailed(e as u32)
+}
+
+/// Converts an error from map_placed() while opening a mapping
+fn map_open_error(e: nix::Error, placement: Option<Placement>) -> ShmemError {
+    match (placement, e) {
+        (Some(Placement::Fixed(addr)), nix::Error::EEXIST) => ShmemError::AddressInUse(addr),
+        _ => ShmemError::MapOpenFailed(e as u32),
     }
 }
 
 /// Creates a mapping specified by the uid and size
-pub fn create_mapping(unique_id: &str, map_size: usize) -> Result<MapData, ShmemError> {
+pub fn create_mapping(
+    unique_id: &str,
+    map_size: usize,
+    ext: &ShmemConfExt,
+) -> Result<MapData, ShmemError> {
     //Create shared memory file descriptor
     debug!("Creating persistent mapping at {}", unique_id);
 
-    let nz_map_size = NonZeroUsize::new(map_size).ok_or(ShmemError::MapSizeZero)?;
+    if map_size == 0 {
+        return Err(ShmemError::MapSizeZero);
+    }
+    // Windows only describe what to open
+    if ext.window.is_some() {
+        return Err(ShmemError::MapCreateFailed(libc::EINVAL as _));
+    }
 
-    let shmem_fd = match shm_open(
-        unique_id, //Unique name that usualy pops up in /dev/shm/
-        OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_RDWR, //create exclusively (error if collision) and read/write to allow resize
-        Mode::S_IRWXU | Mode::S_IRWXG | Mode::S_IRWXO,  //Permission allow user+rw
-    ) {
-        Ok(v) => {
-            trace!(
-                "shm_open({unique_id}, {:X}, {:X}) == {v:?}",
-                OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_RDWR,
-                Mode::S_IRWXU | Mode::S_IRWXG | Mode::S_IRWXO,
-            );
-            v
+    #[cfg(target_os = "linux")]
+    let map_size = match ext.huge_pages {
+        // shm_open() objects live on a tmpfs which cannot hold huge pages
+        Some(_) if ext.backend == Backend::Shm => {
+            return Err(ShmemError::HugePagesUnavailable(libc::EOPNOTSUPP as _))
+        }
+        Some(huge_pages) => map_size.next_multiple_of(huge_pages.bytes()),
+        None => map_size,
+    };
+
+    if ext.backend == Backend::Anonymous {
+        return create_anonymous(unique_id, map_size, ext);
+    }
+
+    // Only objects we create get the configured permissions
+    let mut created = true;
+    let shmem_fd = match ext.backend {
+        Backend::Shm => match shm_open(
+            unique_id, //Unique name that usualy pops up in /dev/shm/
+            OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_RDWR, //create exclusively (error if collision) and read/write to allow resize
+            ext.mode(),                                     //Permissions, filtered by the umask
+        ) {
+            Ok(v) => {
+                trace!(
+                    "shm_open({unique_id}, {:X}, {:o}) == {v:?}",
+                    OFlag::O_CREAT | OFlag::O_EXCL | OFlag::O_RDWR,
+                    ext.mode(),
+                );
+                v
+            }
+            Err(nix::Error::EEXIST) => return Err(ShmemError::MappingIdExists),
+            Err(e) => return Err(ShmemError::MapCreateFailed(e as u32)),
+        },
+        #[cfg(target_os = "linux")]
+        Backend::Memfd => crate::memfd::create_fd(unique_id, ext.huge_pages)?,
+        Backend::File => {
+            let res = match OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create_new(true)
+                .mode(ext.mode().bits() as _)
+                .open(unique_id)
+            {
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    created = false;
+                    OpenOptions::new().read(true).write(true).open(unique_id)
+                }
+                res => res,
+            };
+            match res {
+                Ok(f) => {
+                    trace!("open({unique_id}, O_RDWR) == {f:?}, created {}", created);
+                    OwnedFd::from(f)
+                }
+                Err(e) => {
+                    return Err(ShmemError::MapCreateFailed(
+                        e.raw_os_error().unwrap_or(0) as _
+                    ))
+                }
+            }
         }
-        Err(nix::Error::EEXIST) => return Err(ShmemError::MappingIdExists),
-        Err(e) => return Err(ShmemError::MapCreateFailed(e as u32)),
+        Backend::Fd | Backend::Anonymous => return Err(ShmemError::NoLinkOrOsId),
+    };
+
+    // Tell reclaim_segment() we are alive, it skips objects that were not truncated yet
+    let creation_lock = match ext.backend {
+        Backend::Shm => shm_open(unique_id, OFlag::O_RDONLY, Mode::empty()).ok(),
+        _ => None,
     };
+    if let Some(ref fd) = creation_lock {
+        let _res = unsafe { libc::flock(fd.as_raw_fd(), libc::LOCK_SH) };
+        trace!("flock({:?}, LOCK_SH) == {}", fd, _res);
+    }
 
     let mut new_map: MapData = MapData {
-        owner: true,
+        // Backing files are persistent, they are only deleted if ownership is explicitly taken
+        owner: ext.backend != Backend::File,
+        backend: ext.backend,
         unique_id: String::from(unique_id),
-        map_fd: shmem_fd,
+        map_fd: None,
+        creation_lock,
         map_size,
         map_ptr: NonNull::dangling(),
+        map_prot: ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+        page_size: fd_page_size(&shmem_fd),
+        placement: None,
+        data_offset: 0,
+        window_offset: None,
+        ring_buffer: false,
+        guard_len: 0,
+        options: MapOptions::default(),
     };
 
+    //Huge pages for backing files come from the filesystem they live on
+    #[cfg(target_os = "linux")]
+    if let Some(huge_pages) = ext.huge_pages {
+        if new_map.page_size != huge_pages.bytes() {
+            return Err(ShmemError::HugePagesUnavailable(libc::EINVAL as _));
+        }
+    }
+
+    //Existing backing files keep their content, only grow them if needed
+    if new_map.backend == Backend::File {
+        match fstat(shmem_fd.as_raw_fd()) {
+            Ok(v) if v.st_size as usize > new_map.map_size => new_map.map_size = v.st_size as _,
+            Ok(_) => {}
+            Err(e) => return Err(ShmemError::MapCreateFailed(e as u32)),
+        };
+    }
+    //The second copy of a ring buffer must start on a page boundary
+    if ext.ring_buffer {
+        new_map.map_size = new_map.map_size.next_multiple_of(new_map.page_size);
+    }
+    let nz_map_size = NonZeroUsize::new(new_map.map_size).ok_or(ShmemError::MapSizeZero)?;
+
     //Enlarge the memory descriptor file size to the requested map size
     debug!("Creating memory mapping");
-    trace!("ftruncate({:?}, {})", new_map.map_fd, new_map.map_size);
-    match ftruncate(&new_map.map_fd, new_map.map_size as _) {
+    trace!("ftruncate({:?}, {})", shmem_fd, new_map.map_size);
+    match ftruncate(&shmem_fd, new_map.map_size as _) {
         Ok(_) => {}
         Err(e) => return Err(ShmemError::UnknownOsError(e as u32)),
     };
 
-    #[cfg(target_os = "linux")]
-    if let Err(e) = {
-        fchmod(
-            new_map.map_fd.as_raw_fd(),
-            Mode::S_IRWXU | Mode::S_IRWXG | Mode::S_IRWXO,
-        )
-    } {
-        tracing::error!("fchmod: {e}");
-        return Err(ShmemError::UnknownOsError(e as _));
-    };
+    if created {
+        if let Err(e) = ext.apply_permissions(shmem_fd.as_fd(), ext.mode()) {
+            return Err(ShmemError::MapPermissionsFailed(e as u32));
+        }
+    }
 
     //Put the mapping in our address space
     debug!("Loading mapping into address space");
-    new_map.map_ptr = match unsafe {
-        mmap(
-            None,                                         //Desired addr
-            nz_map_size,                                  //size of mapping
-            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE, //Permissions on pages
-            MapFlags::MAP_SHARED,                         //What kind of mapping
-            &new_map.map_fd,                              //fd
-            0,                                            //Offset into fd
+    let layout = Layout::new(ext, new_map.page_size);
+    new_map.map_ptr = match map_object(
+        ext.placement,                                //Desired addr
+        nz_map_size,                                  //size of mapping
+        ProtFlags::PROT_READ | ProtFlags::PROT_WRITE, //Permissions on pages
+        MapFlags::MAP_SHARED | ext.map_flags(),       //What kind of mapping
+        Some(shmem_fd.as_fd()),                       //fd
+        0,                                            //Offset into fd
+        layout,                                       //Ring buffer and guard pages
+    ) {
+        Ok(v) => v,
+        Err(e) => return Err(map_create_error(e, ext)),
+    };
+    new_map.map_fd = Some(shmem_fd);
+    new_map.ring_buffer = layout.ring_buffer;
+    new_map.guard_len = layout.guard_len;
+
+    new_map.placement = ext.placement;
+    apply_map_options(&mut new_map, ext)?;
+
+    Ok(new_map)
+}
+
+/// Creates a shared mapping that is not backed by any named object
+fn create_anonymous(
+    unique_id: &str,
+    map_size: usize,
+    ext: &ShmemConfExt,
+) -> Result<MapData, ShmemError> {
+    let nz_map_size = NonZeroUsize::new(map_size).ok_or(ShmemError::MapSizeZero)?;
+
+    // There is no object we could map a second time
+    if ext.ring_buffer {
+        return Err(ShmemError::MapCreateFailed(libc::EINVAL as _));
+    }
+
+    #[allow(unused_mut)]
+    let mut flags = MapFlags::MAP_SHARED | ext.map_flags();
+    #[allow(unused_mut)]
+    let mut map_page_size = page_size();
+    #[cfg(target_os = "linux")]
+    if let Some(huge_pages) = ext.huge_pages {
+        flags |= huge_pages.map_flags();
+        map_page_size = huge_pages.bytes();
+    }
+
+    debug!("Creating anonymous mapping");
+    let layout = Layout::new(ext, map_page_size);
+    let map_ptr = match map_object(
+        ext.placement,                                //Desired addr
+        nz_map_size,                                  //size of mapping
+        ProtFlags::PROT_READ | ProtFlags::PROT_WRITE, //Permissions on pages
+        flags,                                        //What kind of mapping
+        None,                                         //No fd
+        0,                                            //Offset into fd
+        layout,                                       //Guard pages
+    ) {
+        Ok(v) => v,
+        Err(e) => return Err(map_create_error(e, ext)),
+    };
+
+    let mut new_map = MapData {
+        owner: true,
+        backend: Backend::Anonymous,
+        unique_id: String::from(unique_id),
+        map_fd: None,
+        creation_lock: None,
+        map_size,
+        map_ptr,
+        map_prot: ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
+        page_size: map_page_size,
+        placement: ext.placement,
+        data_offset: 0,
+        window_offset: None,
+        ring_buffer: false,
+        guard_len: layout.guard_len,
+        options: MapOptions::default(),
+    };
+    apply_map_options(&mut new_map, ext)?;
+
+    Ok(new_map)
+}
+
+/// Removes the name of an existing mapping so it cannot be opened anymore
+pub fn unlink_mapping(unique_id: &str, ext: &ShmemConfExt) -> Result<(), ShmemError> {
+    match ext.backend {
+        Backend::Shm => {
+            trace!("shm_unlink({})", unique_id);
+            match shm_unlink(unique_id) {
+                Ok(_) | Err(nix::Error::ENOENT) => Ok(()),
+                Err(e) => Err(ShmemError::UnknownOsError(e as u32)),
+            }
+        }
+        Backend::File => {
+            trace!("unlink({})", unique_id);
+            match remove_file(unique_id) {
+                Ok(_) => Ok(()),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+                Err(e) => Err(ShmemError::UnknownOsError(
+                    e.raw_os_error().unwrap_or(0) as _
+                )),
+            }
+        }
+        // Nothing to unlink
+        _ => Ok(()),
+    }
+}
+
+/// Converts a timestamp from `stat()`
+fn system_time(sec: i64, nsec: i64) -> SystemTime {
+    UNIX_EPOCH + Duration::new(sec.max(0) as u64, nsec as u32)
+}
+
+/// Returns when the object behind `fd` was created if its filesystem records it
+#[cfg(target_os = "linux")]
+fn birth_time(fd: BorrowedFd<'_>) -> Option<SystemTime> {
+    let mut stx = std::mem::MaybeUninit::<libc::statx>::uninit();
+    let res = unsafe {
+        libc::statx(
+            fd.as_raw_fd(),
+            b"\0".as_ptr() as *const libc::c_char,
+            libc::AT_EMPTY_PATH,
+            libc::STATX_BTIME,
+            stx.as_mut_ptr(),
         )
-    } {
-        Ok(v) => {
-            trace!(
-                "mmap(NULL, {}, {:X}, {:X}, {:?}, 0) == {:p}",
-                new_map.map_size,
-                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
-                MapFlags::MAP_SHARED,
-                new_map.map_fd,
-                v
-            );
-            v
+    };
+    if res != 0 {
+        return None;
+    }
+    let stx = unsafe { stx.assume_init() };
+    if stx.stx_mask & libc::STATX_BTIME == 0 {
+        return None;
+    }
+    Some(system_time(
+        stx.stx_btime.tv_sec,
+        stx.stx_btime.tv_nsec as i64,
+    ))
+}
+
+/// Returns the directory in which the object named `unique_id` shows up
+pub(crate) fn os_id_dir(unique_id: &str, ext: &ShmemConfExt) -> Option<PathBuf> {
+    match ext.backend {
+        #[cfg(target_os = "linux")]
+        Backend::Shm => Some(PathBuf::from("/dev/shm")),
+        Backend::File => match Path::new(unique_id).parent() {
+            Some(dir) if dir.as_os_str().is_empty() => Some(PathBuf::from(".")),
+            dir => dir.map(Path::to_path_buf),
+        },
+        _ => None,
+    }
+}
+
+/// Wakes up when entries of a directory are created or modified
+#[cfg(target_os = "linux")]
+pub(crate) struct DirWatcher {
+    inotify_fd: Option<OwnedFd>,
+}
+
+#[cfg(target_os = "linux")]
+impl DirWatcher {
+    /// Starts watching `dir`, falls back to sleeping if inotify is unavailable
+    pub fn new(dir: &Path) -> Self {
+        use std::os::fd::FromRawFd;
+        use std::os::unix::ffi::OsStrExt;
+
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        trace!("inotify_init1(IN_CLOEXEC | IN_NONBLOCK) == {}", fd);
+        if fd < 0 {
+            return Self { inotify_fd: None };
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let path = match std::ffi::CString::new(dir.as_os_str().as_bytes()) {
+            Ok(v) => v,
+            Err(_) => return Self { inotify_fd: None },
+        };
+        let mask = libc::IN_CREATE | libc::IN_MOVED_TO | libc::IN_MODIFY | libc::IN_CLOSE_WRITE;
+        let res = unsafe { libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) };
+        trace!(
+            "inotify_add_watch({:?}, {:?}, {:X}) == {}",
+            fd,
+            dir,
+            mask,
+            res
+        );
+        Self {
+            inotify_fd: (res >= 0).then_some(fd),
+        }
+    }
+
+    /// Waits for a change in the directory, at most `timeout`
+    pub fn wait(&self, timeout: Duration) {
+        let fd = match self.inotify_fd.as_ref() {
+            Some(fd) => fd.as_raw_fd(),
+            None => return std::thread::sleep(timeout),
+        };
+        let mut pfd = libc::pollfd {
+            fd,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = timeout.as_millis().clamp(1, libc::c_int::MAX as u128) as libc::c_int;
+        let _res = unsafe { libc::poll(&mut pfd, 1, timeout_ms) };
+        trace!("poll({}, POLLIN, {}) == {}", fd, timeout_ms, _res);
+
+        // Drain the events, we only care that something happened
+        let mut buf = [0u8; 4096];
+        while unsafe { libc::read(fd, buf.as_mut_ptr() as *mut c_void, buf.len()) } > 0 {}
+    }
+}
+
+/// Lists the os_id of the shared memory objects starting with `prefix`
+#[cfg(target_os = "linux")]
+pub fn list_segments(prefix: &str) -> Result<Vec<String>, ShmemError> {
+    let mut ids = Vec::new();
+    for entry in std::fs::read_dir("/dev/shm").map_err(ShmemError::ListFailed)? {
+        let entry = entry.map_err(ShmemError::ListFailed)?;
+        // Symbolic links and directories are not shared memory objects
+        if !entry.file_type().is_ok_and(|t| t.is_file()) {
+            continue;
+        }
+        if let Some(name) = entry.file_name().to_str() {
+            let unique_id = format!("/{name}");
+            if unique_id.starts_with(prefix) {
+                ids.push(unique_id);
+            }
+        }
+    }
+    ids.sort();
+    Ok(ids)
+}
+
+/// Lists the os_id of the shared memory objects starting with `prefix`
+///
+/// Only Linux exposes its shared memory objects through a filesystem
+#[cfg(not(target_os = "linux"))]
+pub fn list_segments(_prefix: &str) -> Result<Vec<String>, ShmemError> {
+    Err(ShmemError::ListFailed(
+        std::io::ErrorKind::Unsupported.into(),
+    ))
+}
+
+/// Describes the shared memory object, `None` if it does not exist or cannot be described
+///
+/// Objects deleted or replaced by something else while they are listed are skipped.
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn segment_info(unique_id: &str) -> Option<SegmentInfo> {
+    let (st, header) = match shm_open(unique_id, OFlag::O_RDONLY, Mode::empty()) {
+        Ok(fd) => match fstat(fd.as_raw_fd()) {
+            Ok(st) => (st, read_header(&fd, st.st_size as usize)),
+            Err(_e) => {
+                debug!("Cannot stat '{}' : {}", unique_id, _e);
+                return None;
+            }
+        },
+        // Objects we cannot read can still be described
+        #[cfg(target_os = "linux")]
+        Err(nix::Error::EACCES) => {
+            match nix::sys::stat::lstat(format!("/dev/shm{unique_id}").as_str()) {
+                Ok(st) if st.st_mode & libc::S_IFMT == libc::S_IFREG => (st, None),
+                _ => return None,
+            }
+        }
+        Err(_e) => {
+            debug!("Cannot open '{}' to describe it : {}", unique_id, _e);
+            return None;
+        }
+    };
+    Some(SegmentInfo {
+        os_id: unique_id.to_string(),
+        size: st.st_size as usize,
+        uid: st.st_uid,
+        mode: st.st_mode as u32 & 0o7777,
+        modified: system_time(st.st_mtime as i64, st.st_mtime_nsec as i64),
+        header,
+    })
+}
+
+/// Deletes the shared memory object if no handle is attached and its creator is dead
+pub(crate) fn reclaim_segment(unique_id: &str) -> Result<SegmentState, ShmemError> {
+    let fd = match shm_open(unique_id, OFlag::O_RDONLY, Mode::empty()) {
+        Ok(v) => v,
+        Err(nix::Error::ENOENT) => return Ok(SegmentState::Missing),
+        // Not ours to judge
+        Err(_e) => {
+            debug!("Cannot open '{}' to check it : {}", unique_id, _e);
+            return Ok(SegmentState::Alive);
         }
-        Err(e) => return Err(ShmemError::MapCreateFailed(e as u32)),
     };
 
-    Ok(new_map)
+    // Every handle holds a shared lock, see MapData::lock_attached(). Keeping the lock exclusive until the object is
+    // deleted makes handles that attach in the meantime notice it.
+    let res = unsafe { libc::flock(fd.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    trace!("flock({:?}, LOCK_EX | LOCK_NB) == {}", fd, res);
+    if res != 0 {
+        return Ok(SegmentState::Alive);
+    }
+
+    // Objects that were not truncated yet are still being created
+    let size = match fstat(fd.as_raw_fd()) {
+        Ok(v) if v.st_size > 0 => v.st_size as usize,
+        _ => return Ok(SegmentState::Alive),
+    };
+
+    // Mappings without a lock either outlived their creator, or were created by another program, an older version of
+    // this crate or a creator that gave up ownership. Only the ones whose creator is known to be dead are deleted.
+    let (pid, created) = match read_header(&fd, size) {
+        Some(header) => (header.creator_pid, Some(header.created)),
+        None => match default_id_pid(unique_id) {
+            Some(pid) => (pid, None),
+            None => return Ok(SegmentState::Alive),
+        },
+    };
+    if process_alive(pid, created) {
+        return Ok(SegmentState::Alive);
+    }
+
+    trace!("shm_unlink({})", unique_id);
+    match shm_unlink(unique_id) {
+        Ok(_) => Ok(SegmentState::Reclaimed),
+        Err(nix::Error::ENOENT) => Ok(SegmentState::Missing),
+        // Objects of other users in a sticky directory
+        Err(nix::Error::EACCES) | Err(nix::Error::EPERM) => {
+            debug!("Not allowed to delete '{}'", unique_id);
+            Ok(SegmentState::Alive)
+        }
+        Err(e) => Err(ShmemError::UnknownOsError(e as u32)),
+    }
+}
+
+/// Reads the header at the start of the object if it has one
+pub(crate) fn read_header(fd: &OwnedFd, size: usize) -> Option<ShmemHeader> {
+    if size < header::HEADER_LEN {
+        return None;
+    }
+    let len = NonZeroUsize::new(header::HEADER_LEN)?;
+    let ptr = unsafe { mmap(None, len, ProtFlags::PROT_READ, MapFlags::MAP_SHARED, fd, 0) }.ok()?;
+    let header = unsafe { header::read_checked(ptr.as_ptr() as *const u8, size) };
+    let _ = unsafe { munmap(ptr, len.get()) };
+    header
+}
+
+/// Returns the pid that a default os_id was made from
+fn default_id_pid(unique_id: &str) -> Option<u32> {
+    let id = unique_id.strip_prefix("/shmem_")?;
+    let pid = match id.split_once('_') {
+        Some((pid, n)) if usize::from_str_radix(n, 16).is_ok() => pid,
+        Some(_) => return None,
+        None => id,
+    };
+    u32::from_str_radix(pid, 16).ok()
+}
+
+/// Returns whether the process that created a mapping at `created` may still be running
+fn process_alive(pid: u32, created: Option<SystemTime>) -> bool {
+    let res = unsafe { libc::kill(pid as libc::pid_t, 0) };
+    if res != 0 && nix::Error::last() == nix::Error::ESRCH {
+        return false;
+    }
+    // The pid belongs to a process started after the mapping was created
+    #[cfg(target_os = "linux")]
+    if let (Some(created), Some(started)) = (created, process_start_time(pid)) {
+        // The boot time is only known to the second
+        return started <= created + Duration::from_secs(1);
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = created;
+    true
+}
+
+/// Returns when a process started
+#[cfg(target_os = "linux")]
+fn process_start_time(pid: u32) -> Option<SystemTime> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    // The command name may contain spaces and parentheses, starttime is the 20th field after it
+    let start_ticks: u64 = stat
+        .rsplit_once(')')?
+        .1
+        .split_whitespace()
+        .nth(19)?
+        .parse()
+        .ok()?;
+    let boot_time: u64 = std::fs::read_to_string("/proc/stat")
+        .ok()?
+        .lines()
+        .find_map(|l| l.strip_prefix("btime "))?
+        .trim()
+        .parse()
+        .ok()?;
+    let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks_per_sec <= 0 {
+        return None;
+    }
+    let ticks_per_sec = ticks_per_sec as u64;
+    Some(
+        UNIX_EPOCH
+            + Duration::from_secs(boot_time + start_ticks / ticks_per_sec)
+            + Duration::from_nanos(start_ticks % ticks_per_sec * 1_000_000_000 / ticks_per_sec),
+    )
 }
 
 /// Opens an existing mapping specified by its uid
 pub fn open_mapping(
     unique_id: &str,
     _map_size: usize,
-    _ext: &ShmemConfExt,
+    read_only: bool,
+    ext: &ShmemConfExt,
 ) -> Result<MapData, ShmemError> {
-    //Open shared memory
-    debug!("Openning persistent mapping at {}", unique_id);
-    let shmem_fd = match shm_open(
-        unique_id,
-        OFlag::O_RDWR, //Open read write
-        Mode::S_IRWXU | Mode::S_IRWXG | Mode::S_IRWXO,
-    ) {
-        Ok(v) => {
-            trace!(
-                "shm_open({unique_id}, {:X}, {:X}) == {v:?}",
-                OFlag::O_RDWR,
+    let oflag = if read_only {
+        OFlag::O_RDONLY
+    } else {
+        OFlag::O_RDWR
+    };
+
+    let shmem_fd = match ext.backend {
+        Backend::Shm => {
+            //Open shared memory
+            debug!("Openning persistent mapping at {}", unique_id);
+            match shm_open(
+                unique_id,
+                oflag, //Open read write or read only
                 Mode::S_IRWXU | Mode::S_IRWXG | Mode::S_IRWXO,
-            );
+            ) {
+                Ok(v) => {
+                    trace!(
+                        "shm_open({unique_id}, {:X}, {:X}) == {v:?}",
+                        oflag,
+                        Mode::S_IRWXU | Mode::S_IRWXG | Mode::S_IRWXO,
+                    );
+                    v
+                }
+                Err(e) => return Err(ShmemError::MapOpenFailed(e as u32)),
+            }
+        }
+        Backend::File => {
+            debug!("Openning backing file at {}", unique_id);
+            match OpenOptions::new()
+                .read(true)
+                .write(!read_only)
+                .open(unique_id)
+            {
+                Ok(f) => {
+                    trace!("open({unique_id}, {:X}) == {f:?}", oflag);
+                    OwnedFd::from(f)
+                }
+                Err(e) => {
+                    return Err(ShmemError::MapOpenFailed(e.raw_os_error().unwrap_or(0) as _))
+                }
+            }
+        }
+        // These have no name that can be opened
+        _ => return Err(ShmemError::MapOpenFailed(libc::ENOENT as _)),
+    };
+
+    map_existing(unique_id, shmem_fd, ext.backend, read_only, ext)
+}
+
+/// Opens an existing mapping from a file descriptor
+pub fn open_fd(
+    unique_id: &str,
+    fd: BorrowedFd<'_>,
+    read_only: bool,
+    ext: &ShmemConfExt,
+) -> Result<MapData, ShmemError> {
+    debug!("Openning mapping from file descriptor {}", fd.as_raw_fd());
+    let shmem_fd = match fd.try_clone_to_owned() {
+        Ok(v) => {
+            trace!("dup({}) == {v:?}", fd.as_raw_fd());
             v
         }
-        Err(e) => return Err(ShmemError::MapOpenFailed(e as u32)),
+        Err(e) => return Err(ShmemError::MapOpenFailed(e.raw_os_error().unwrap_or(0) as _)),
     };
 
+    map_existing(unique_id, shmem_fd, Backend::Fd, read_only, ext)
+}
+
+/// Maps an already opened object into our address space
+fn map_existing(
+    unique_id: &str,
+    shmem_fd: OwnedFd,
+    backend: Backend,
+    read_only: bool,
+    ext: &ShmemConfExt,
+) -> Result<MapData, ShmemError> {
+    let placement = ext.placement;
     let mut new_map: MapData = MapData {
         owner: false,
+        backend,
         unique_id: String::from(unique_id),
-        map_fd: shmem_fd,
+        map_fd: None,
+        creation_lock: None,
         map_size: 0,
         map_ptr: NonNull::dangling(),
+        map_prot: if read_only {
+            ProtFlags::PROT_READ
+        } else {
+            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE
+        },
+        page_size: fd_page_size(&shmem_fd),
+        placement: None,
+        data_offset: 0,
+        window_offset: None,
+        ring_buffer: false,
+        guard_len: 0,
+        options: MapOptions::default(),
     };
 
     //Get mmap size
-    new_map.map_size = match fstat(new_map.map_fd.as_raw_fd()) {
+    new_map.map_size = match fstat(shmem_fd.as_raw_fd()) {
         Ok(v) => v.st_size as usize,
         Err(e) => return Err(ShmemError::MapOpenFailed(e as u32)),
     };
 
-    let nz_map_size = NonZeroUsize::new(new_map.map_size).ok_or(ShmemError::MapSizeZero)?;
+    if !ext.required_seals.is_empty() {
+        match fd_seals(shmem_fd.as_fd()) {
+            Ok(v) if v.contains(ext.required_seals) => {}
+            Ok(_) => return Err(ShmemError::NotSealed),
+            Err(e) => return Err(ShmemError::MapOpenFailed(e as u32)),
+        }
+    }
+
+    //Only map the requested window, starting from the page that contains it
+    let mut map_offset = 0;
+    if let Some((offset, len)) = ext.window {
+        if offset
+            .checked_add(len)
+            .is_none_or(|end| end > new_map.map_size)
+        {
+            return Err(ShmemError::RangeOutOfBounds { offset, len });
+        }
+        map_offset = offset - (offset % new_map.page_size);
+        new_map.data_offset = offset - map_offset;
+        new_map.window_offset = Some(offset);
+        new_map.map_size = len;
+    }
+
+    let nz_map_size =
+        NonZeroUsize::new(new_map.data_offset + new_map.map_size).ok_or(ShmemError::MapSizeZero)?;
+
+    //Both copies of a ring buffer must be made of whole pages
+    if ext.ring_buffer
+        && (new_map.data_offset != 0 || !new_map.map_size.is_multiple_of(new_map.page_size))
+    {
+        return Err(ShmemError::RangeNotAligned {
+            offset: map_offset + new_map.data_offset,
+            len: new_map.map_size,
+        });
+    }
 
     //Map memory into our address space
     debug!("Loading mapping into address space");
-    new_map.map_ptr = match unsafe {
-        mmap(
-            None,                                         //Desired addr
-            nz_map_size,                                  //size of mapping
-            ProtFlags::PROT_READ | ProtFlags::PROT_WRITE, //Permissions on pages
-            MapFlags::MAP_SHARED,                         //What kind of mapping
-            &new_map.map_fd,                              //fd
-            0,                                            //Offset into fd
-        )
-    } {
-        Ok(v) => {
-            trace!(
-                "mmap(NULL, {}, {:X}, {:X}, {:?}, 0) == {:p}",
-                new_map.map_size,
-                ProtFlags::PROT_READ | ProtFlags::PROT_WRITE,
-                MapFlags::MAP_SHARED,
-                new_map.map_fd,
-                v
-            );
-            v
-        }
-        Err(e) => return Err(ShmemError::MapOpenFailed(e as u32)),
+    let layout = Layout::new(ext, new_map.page_size);
+    new_map.map_ptr = match map_object(
+        placement,                              //Desired addr
+        nz_map_size,                            //size of mapping
+        new_map.map_prot,                       //Permissions on pages
+        MapFlags::MAP_SHARED | ext.map_flags(), //What kind of mapping
+        Some(shmem_fd.as_fd()),                 //fd
+        map_offset,                             //Offset into fd
+        layout,                                 //Ring buffer and guard pages
+    ) {
+        Ok(v) => v,
+        Err(e) => return Err(map_open_error(e, placement)),
     };
+    new_map.map_fd = Some(shmem_fd);
+    new_map.ring_buffer = layout.ring_buffer;
+    new_map.guard_len = layout.guard_len;
+
+    new_map.placement = placement;
+    apply_map_options(&mut new_map, ext)?;
 
     Ok(new_map)
 }
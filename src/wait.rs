@@ -0,0 +1,143 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crate::{debug, os_impl, ReadOnlyShmem, Result, Shmem, ShmemConf, ShmemError, Timeout};
+
+#[cfg(target_os = "linux")]
+use crate::unix::DirWatcher;
+
+/// How often the initialized flag of the header is checked, writes to the mapping cannot be watched
+const INIT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// How often the os_id or flink is checked when it cannot be watched
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How `open()` retries when the os_id read from a flink cannot be opened yet
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct RetryPolicy {
+    /// Number of retries before giving up
+    pub attempts: u32,
+    /// Time to sleep before every retry
+    pub delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 5,
+            delay: Duration::from_millis(50),
+        }
+    }
+}
+
+impl ShmemConf {
+    /// Sets how `open()` retries when the os_id read from a flink cannot be opened yet
+    ///
+    /// This happens when the creator has not finished writing the flink. Defaults to 5 retries 50ms apart. The number of
+    /// attempts also bounds how many times `open_or_create()` replaces a mapping when `force_replace()` is used.
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.retry = policy;
+        self
+    }
+
+    /// Opens an existing mapping, waiting for it to be created and initialized
+    ///
+    /// Waits until the os_id or flink exists, then until the creator calls `Shmem::set_initialized()` if the
+    /// mapping has a header. On Linux, the creation of the os_id or flink is watched with inotify instead of polling.
+    /// Fails with `WaitTimedOut` if this takes longer than `timeout`.
+    ///
+    /// Configuring `ShmemConf::header()` for a mapping that has no header makes this wait for the header until the
+    /// timeout.
+    pub fn open_wait(self, timeout: Timeout) -> Result<Shmem> {
+        self.open_wait_inner(timeout, false)
+    }
+
+    /// Opens an existing mapping in read-only mode, waiting for it to be created and initialized
+    ///
+    /// See `open_wait()` and `open_read_only()`
+    pub fn open_read_only_wait(self, timeout: Timeout) -> Result<ReadOnlyShmem> {
+        Ok(ReadOnlyShmem {
+            inner: self.open_wait_inner(timeout, true)?,
+        })
+    }
+
+    fn open_wait_inner(self, timeout: Timeout, read_only: bool) -> Result<Shmem> {
+        let deadline = match timeout {
+            Timeout::Infinite => None,
+            Timeout::Val(d) => Some(Instant::now() + d),
+        };
+        let remaining = || deadline.map(|d| d.saturating_duration_since(Instant::now()));
+
+        // Watch before the first attempt so creations that happen right after it wake us up
+        let watcher = self.watched_dir().map(|dir| DirWatcher::new(&dir));
+
+        loop {
+            match self.clone().open_inner(read_only) {
+                Ok(shmem) => loop {
+                    if shmem.is_initialized() {
+                        return Ok(shmem);
+                    }
+                    match remaining() {
+                        Some(Duration::ZERO) => return Err(ShmemError::WaitTimedOut),
+                        Some(r) => std::thread::sleep(r.min(INIT_POLL_INTERVAL)),
+                        None => std::thread::sleep(INIT_POLL_INTERVAL),
+                    }
+                },
+                Err(e) if is_pending(&e) => {}
+                Err(e) => return Err(e),
+            }
+
+            let wait = match remaining() {
+                Some(Duration::ZERO) => return Err(ShmemError::WaitTimedOut),
+                Some(r) => r.min(POLL_INTERVAL),
+                None => POLL_INTERVAL,
+            };
+            debug!("Waiting for the mapping to be created");
+            match watcher.as_ref() {
+                Some(watcher) => watcher.wait(wait),
+                None => std::thread::sleep(wait),
+            }
+        }
+    }
+
+    /// Returns the directory in which the os_id or flink will be created
+    fn watched_dir(&self) -> Option<PathBuf> {
+        let path = match (self.flink_path.as_ref(), self.os_id.as_ref()) {
+            (Some(flink_path), _) => flink_path.as_path(),
+            (None, Some(os_id)) => return os_impl::os_id_dir(os_id, &self.ext),
+            (None, None) => return None,
+        };
+        match path.parent() {
+            Some(dir) if dir.as_os_str().is_empty() => Some(PathBuf::from(".")),
+            Some(dir) => Some(dir.to_path_buf()),
+            None => None,
+        }
+    }
+}
+
+/// Returns whether opening failed because the mapping is not fully created yet
+fn is_pending(e: &ShmemError) -> bool {
+    match e {
+        ShmemError::LinkOpenFailed(e) => e.kind() == ErrorKind::NotFound,
+        ShmemError::LinkReadFailed(e) => e.kind() == ErrorKind::InvalidData,
+        ShmemError::MapOpenFailed(e) => *e == os_impl::MAP_NOT_FOUND,
+        // The creator has not set the size or written the header yet
+        ShmemError::MapSizeZero | ShmemError::HeaderInvalid => true,
+        _ => false,
+    }
+}
+
+/// Falls back to polling on platforms without inotify
+#[cfg(not(target_os = "linux"))]
+struct DirWatcher;
+
+#[cfg(not(target_os = "linux"))]
+impl DirWatcher {
+    fn new(_dir: &std::path::Path) -> Self {
+        DirWatcher
+    }
+
+    fn wait(&self, timeout: Duration) {
+        std::thread::sleep(timeout);
+    }
+}
@@ -6,13 +6,26 @@ use std::path::PathBuf;
 use crate::{debug, trace, ShmemConf};
 use win_sys::*;
 
-use crate::ShmemError;
+use crate::reclaim::SegmentState;
+use crate::{SegmentInfo, ShmemError};
 
 #[derive(Clone, Default)]
 pub struct ShmemConfExt {
     allow_raw: bool,
 }
 
+impl ShmemConfExt {
+    /// Name of the backend recorded in flinks
+    pub(crate) fn backend_name(&self) -> &'static str {
+        "mapping"
+    }
+
+    /// Whether the mapping layout leaves room for a header
+    pub(crate) fn supports_header(&self) -> bool {
+        true
+    }
+}
+
 impl ShmemConf {
     /// If set to true, enables openning raw shared memory that is not managed by this crate
     pub fn allow_raw(mut self, allow: bool) -> Self {
@@ -59,60 +72,94 @@ impl Drop for MapData {
         // because the file has been renamed. This matches the behavior of shm_unlink()
         // on unix.
         if self.owner {
-            let mut base_path = get_tmp_dir().unwrap();
-
-            // 1. Set file attributes so that it deletes itself once everyone has closed it
-            let file_path = base_path.join(self.unique_id.trim_start_matches('/'));
-            debug!("Setting mapping to delete after everyone has closed it");
-            match OpenOptions::new()
-                .access_mode(GENERIC_READ | GENERIC_WRITE | DELETE)
-                .share_mode((FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE).0)
-                .create(false)
-                .attributes((FILE_ATTRIBUTE_TEMPORARY | FILE_FLAG_DELETE_ON_CLOSE).0)
-                .open(&file_path)
-            {
-                Ok(_) => {
-                    // 2. Rename file to prevent further use
-                    base_path.push(&format!(
-                        "{}_deleted",
-                        self.unique_id.trim_start_matches('/')
-                    ));
-                    debug!(
-                        "Renaming {} to {}",
-                        file_path.to_string_lossy(),
-                        base_path.to_string_lossy()
-                    );
-                    if let Err(_e) = std::fs::rename(&file_path, &base_path) {
-                        debug!(
-                            "Failed to rename persistent_file {} : {}",
-                            file_path.to_string_lossy(),
-                            _e
-                        );
-                    }
-                }
-                Err(_e) => {
-                    debug!(
-                        "Failed to set DELETE_ON_CLOSE on persistent_file {} : {}",
-                        file_path.to_string_lossy(),
-                        _e
-                    );
-                }
-            };
+            delete_persistent_file(get_tmp_dir().unwrap(), &self.unique_id);
         }
     }
 }
 
+/// Marks the file backing a mapping for deletion and renames it so it cannot be opened anymore
+fn delete_persistent_file(mut base_path: PathBuf, unique_id: &str) {
+    // 1. Set file attributes so that it deletes itself once everyone has closed it
+    let file_path = base_path.join(unique_id.trim_start_matches('/'));
+    debug!("Setting mapping to delete after everyone has closed it");
+    match OpenOptions::new()
+        .access_mode(GENERIC_READ | GENERIC_WRITE | DELETE)
+        .share_mode((FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE).0)
+        .create(false)
+        .attributes((FILE_ATTRIBUTE_TEMPORARY | FILE_FLAG_DELETE_ON_CLOSE).0)
+        .open(&file_path)
+    {
+        Ok(_) => {
+            // 2. Rename file to prevent further use
+            base_path.push(&format!("{}_deleted", unique_id.trim_start_matches('/')));
+            debug!(
+                "Renaming {} to {}",
+                file_path.to_string_lossy(),
+                base_path.to_string_lossy()
+            );
+            if let Err(_e) = std::fs::rename(&file_path, &base_path) {
+                debug!(
+                    "Failed to rename persistent_file {} : {}",
+                    file_path.to_string_lossy(),
+                    _e
+                );
+            }
+        }
+        Err(_e) => {
+            debug!(
+                "Failed to set DELETE_ON_CLOSE on persistent_file {} : {}",
+                file_path.to_string_lossy(),
+                _e
+            );
+        }
+    };
+}
+
 impl MapData {
+    /// Address recorded in the header for openers, Windows does not choose addresses
+    pub fn recorded_base(&self) -> Option<(usize, bool)> {
+        None
+    }
+
+    /// Identity of the backing object, Windows relies on the generation of the header
+    pub fn object_id(&self) -> Option<u64> {
+        None
+    }
+
+    /// Maps the view at the address recorded by its creator, Windows does not choose addresses
+    pub fn relocate(
+        &mut self,
+        _base: usize,
+        _fixed: bool,
+        _ext: &ShmemConfExt,
+    ) -> Result<(), ShmemError> {
+        Ok(())
+    }
+
+    pub fn is_owner(&self) -> bool {
+        self.owner
+    }
+
     pub fn set_owner(&mut self, is_owner: bool) -> bool {
         let prev_val = self.owner;
         self.owner = is_owner;
         prev_val
     }
+    /// Windows keeps mappings alive as long as a handle is open, the reference count alone decides the cleanup
+    pub fn lock_attached(&self) -> Result<Option<bool>, ShmemError> {
+        Ok(None)
+    }
+    pub fn is_last_attached(&self) -> Option<bool> {
+        None
+    }
     pub fn as_mut_ptr(&self) -> *mut u8 {
         self.view.as_mut_ptr() as _
     }
 }
 
+/// Error code reported by `MapOpenFailed` when the mapping does not exist
+pub const MAP_NOT_FOUND: u32 = ERROR_FILE_NOT_FOUND.0;
+
 /// Returns the path to a temporary directory in which to store files backing the shared memory. If it
 /// doesn't exist, the directory is created.
 fn get_tmp_dir() -> Result<PathBuf, ShmemError> {
@@ -135,6 +182,7 @@ fn new_map(
     unique_id: &str,
     mut map_size: usize,
     create: bool,
+    read_only: bool,
     allow_raw: bool,
 ) -> Result<MapData, ShmemError> {
     // Create file to back the shared memory
@@ -148,7 +196,7 @@ fn new_map(
 
     let mut opt = OpenOptions::new();
     opt.read(true)
-        .write(true)
+        .write(!read_only)
         .share_mode((FILE_SHARE_READ | FILE_SHARE_WRITE | FILE_SHARE_DELETE).0)
         .attributes((FILE_ATTRIBUTE_TEMPORARY).0);
     if create {
@@ -167,10 +215,15 @@ fn new_map(
             );
             let high_size: u32 = ((map_size as u64 & 0xFFFF_FFFF_0000_0000_u64) >> 32) as u32;
             let low_size: u32 = (map_size as u64 & 0xFFFF_FFFF_u64) as u32;
+            let page_prot = if read_only {
+                PAGE_READONLY
+            } else {
+                PAGE_READWRITE
+            };
             trace!(
                 "CreateFileMapping({:?}, NULL, {:X}, {}, {}, '{}')",
                 HANDLE(f.as_raw_handle() as _),
-                PAGE_READWRITE.0,
+                page_prot.0,
                 high_size,
                 low_size,
                 unique_id,
@@ -179,7 +232,7 @@ fn new_map(
             match CreateFileMapping(
                 HANDLE(f.as_raw_handle() as _),
                 None,
-                PAGE_READWRITE,
+                page_prot,
                 high_size,
                 low_size,
                 unique_id,
@@ -190,6 +243,11 @@ fn new_map(
                 }
                 Err(e) => {
                     let err_code = e.win32_error().unwrap();
+                    // The file we just created does not back anything
+                    if create {
+                        drop(f);
+                        let _ = std::fs::remove_file(&file_path);
+                    }
                     return if err_code == ERROR_ALREADY_EXISTS {
                         Err(ShmemError::MappingIdExists)
                     } else {
@@ -212,13 +270,18 @@ fn new_map(
 
             // This may be a mapping that isnt managed by this crate
             // Try to open the mapping without any backing file
+            let map_access = if read_only {
+                FILE_MAP_READ
+            } else {
+                FILE_MAP_ALL_ACCESS
+            };
             trace!(
                 "OpenFileMappingW({:?}, {}, '{}')",
-                FILE_MAP_ALL_ACCESS,
+                map_access,
                 false,
                 unique_id,
             );
-            match OpenFileMapping(FILE_MAP_ALL_ACCESS, false, unique_id) {
+            match OpenFileMapping(map_access, false, unique_id) {
                 Ok(h) => h,
                 Err(e) => {
                     return Err(ShmemError::MapOpenFailed(e.win32_error().unwrap().0));
@@ -230,12 +293,13 @@ fn new_map(
 
     //Map mapping into address space
     debug!("Loading mapping into address space");
-    trace!(
-        "MapViewOfFile(0x{:X}, {:X}, 0, 0, 0)",
-        map_h,
-        (FILE_MAP_READ | FILE_MAP_WRITE).0,
-    );
-    let map_ptr = match MapViewOfFile(map_h.as_handle(), FILE_MAP_READ | FILE_MAP_WRITE, 0, 0, 0) {
+    let view_access = if read_only {
+        FILE_MAP_READ
+    } else {
+        FILE_MAP_READ | FILE_MAP_WRITE
+    };
+    trace!("MapViewOfFile(0x{:X}, {:X}, 0, 0, 0)", map_h, view_access.0);
+    let map_ptr = match MapViewOfFile(map_h.as_handle(), view_access, 0, 0, 0) {
         Ok(v) => v,
         Err(e) => {
             return Err(if create {
@@ -267,15 +331,57 @@ fn new_map(
 }
 
 //Creates a mapping specified by the uid and size
-pub fn create_mapping(unique_id: &str, map_size: usize) -> Result<MapData, ShmemError> {
-    new_map(unique_id, map_size, true, false)
+pub fn create_mapping(
+    unique_id: &str,
+    map_size: usize,
+    _ext: &ShmemConfExt,
+) -> Result<MapData, ShmemError> {
+    new_map(unique_id, map_size, true, false, false)
+}
+
+//Removes an existing mapping so it cannot be opened anymore
+pub fn unlink_mapping(unique_id: &str, _ext: &ShmemConfExt) -> Result<(), ShmemError> {
+    delete_persistent_file(get_tmp_dir()?, unique_id);
+    Ok(())
 }
 
 //Opens an existing mapping specified by its uid
 pub fn open_mapping(
     unique_id: &str,
     map_size: usize,
+    read_only: bool,
     ext: &ShmemConfExt,
 ) -> Result<MapData, ShmemError> {
-    new_map(unique_id, map_size, false, ext.allow_raw)
+    new_map(unique_id, map_size, false, read_only, ext.allow_raw)
+}
+
+/// Lists the os_id of the shared memory objects starting with `prefix`
+///
+/// Windows does not expose its named mappings
+pub fn list_segments(_prefix: &str) -> Result<Vec<String>, ShmemError> {
+    Err(ShmemError::ListFailed(ErrorKind::Unsupported.into()))
+}
+
+/// Returns the directory in which the mapping shows up
+///
+/// Named mappings do not live on a filesystem
+pub(crate) fn os_id_dir(_unique_id: &str, _ext: &ShmemConfExt) -> Option<PathBuf> {
+    None
+}
+
+/// Describes a mapping
+///
+/// Never called as `list_segments()` is not supported
+pub(crate) fn segment_info(_unique_id: &str) -> Option<SegmentInfo> {
+    None
+}
+
+/// Checks whether a mapping still exists
+///
+/// Windows deletes mappings along with their last handle, they can never be stale
+pub(crate) fn reclaim_segment(unique_id: &str) -> Result<SegmentState, ShmemError> {
+    match new_map(unique_id, 0, false, true, false) {
+        Err(ShmemError::MapOpenFailed(MAP_NOT_FOUND)) => Ok(SegmentState::Missing),
+        _ => Ok(SegmentState::Alive),
+    }
 }
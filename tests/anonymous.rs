@@ -0,0 +1,33 @@
+#![cfg(unix)]
+
+use std::time::Duration;
+
+use shared_memory::{Event, EventInit, EventState, ShmemConf, Timeout};
+
+#[test]
+fn anonymous_fork() {
+    let shmem = ShmemConf::new().size(4096).anonymous().create().unwrap();
+    assert!(shmem.fd().is_none());
+
+    let (evt, used_bytes) = unsafe { Event::new(shmem.as_ptr(), true).unwrap() };
+    let data = unsafe { shmem.as_ptr().add(used_bytes + 8) };
+
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork() failed"),
+        0 => {
+            // Child uses its inherited copy of the mapping
+            let (evt, _) = unsafe { Event::from_existing(shmem.as_ptr()).unwrap() };
+            unsafe { data.write_volatile(0x42) };
+            evt.set(EventState::Signaled).unwrap();
+            unsafe { libc::_exit(0) };
+        }
+        pid => {
+            evt.wait(Timeout::Val(Duration::from_secs(5))).unwrap();
+            assert_eq!(unsafe { data.read_volatile() }, 0x42);
+
+            let mut status = 0;
+            unsafe { libc::waitpid(pid, &mut status, 0) };
+            assert!(libc::WIFEXITED(status));
+        }
+    }
+}
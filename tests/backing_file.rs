@@ -0,0 +1,47 @@
+#![cfg(unix)]
+
+use std::path::Path;
+
+use shared_memory::ShmemConf;
+
+#[test]
+fn backing_file_persistence() {
+    let path = Path::new("backing_file_persistence");
+    let _ = std::fs::remove_file(path);
+
+    {
+        let s = ShmemConf::new()
+            .backing_file(path)
+            .size(4096)
+            .create()
+            .unwrap();
+        assert!(!s.is_owner());
+        assert!(s.len() >= 4096);
+        unsafe { s.as_ptr().add(100).write_volatile(0x42) };
+        s.flush().unwrap();
+        s.flush_range(99, 2).unwrap();
+        assert!(s.flush_range(s.len(), 1).is_err());
+    }
+
+    // The file outlives the mapping and keeps its content
+    assert!(path.is_file());
+    assert_eq!(std::fs::read(path).unwrap()[100], 0x42);
+
+    // Creating again on an existing file keeps its content
+    let s = ShmemConf::new()
+        .backing_file(path)
+        .size(1024)
+        .create()
+        .unwrap();
+    assert!(s.len() >= 4096);
+    unsafe { assert_eq!(s.as_ptr().add(100).read_volatile(), 0x42) };
+
+    let mut s2 = ShmemConf::new().backing_file(path).open().unwrap();
+    assert_eq!(s2.len(), s.len());
+    unsafe { assert_eq!(s2.as_ptr().add(100).read_volatile(), 0x42) };
+
+    // Taking ownership deletes the file
+    s2.set_owner(true);
+    drop(s2);
+    assert!(!path.is_file());
+}
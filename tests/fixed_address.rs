@@ -0,0 +1,93 @@
+#![cfg(unix)]
+
+use shared_memory::{ShmemConf, ShmemError};
+
+/// Finds a free page aligned range of `len` bytes
+fn free_address(len: usize) -> usize {
+    unsafe {
+        let addr = libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_NONE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        assert_ne!(addr, libc::MAP_FAILED);
+        libc::munmap(addr, len);
+        addr as usize
+    }
+}
+
+#[test]
+fn fixed_address_open() {
+    let os_id = "/fixed_address_open";
+    let addr = free_address(1 << 20);
+
+    let mut s1 = ShmemConf::new()
+        .size(4096)
+        .id(os_id)
+        .fixed_address(addr)
+        .header(0, 0)
+        .create()
+        .unwrap();
+    // The payload follows the header that records the address
+    let ptr = s1.as_ptr();
+    assert!(ptr as usize > addr && (ptr as usize) < addr + s1.page_size());
+    assert_eq!(s1.len(), 4096);
+    unsafe { s1.as_ptr().write_volatile(0x42) };
+
+    // The creator still occupies the recorded address
+    match ShmemConf::new().id(os_id).header(0, 0).open() {
+        Err(ShmemError::AddressInUse(a)) => assert_eq!(a, addr),
+        Err(e) => panic!("Unexpected error {}", e),
+        Ok(_) => panic!("Opened mapping at an address that is in use"),
+    }
+
+    // Once it is gone, openers land on the recorded address without asking for it
+    s1.set_owner(false);
+    drop(s1);
+    let mut s2 = ShmemConf::new().id(os_id).header(0, 0).open().unwrap();
+    s2.set_owner(true);
+    assert_eq!(s2.as_ptr(), ptr);
+    assert_eq!(s2.len(), 4096);
+    assert_eq!(s2.header().unwrap().base_address, Some(addr));
+    assert_eq!(unsafe { s2.as_ptr().read_volatile() }, 0x42);
+    assert!(s2.resize(8192).is_err());
+}
+
+#[test]
+fn fixed_address_in_use() {
+    let addr = free_address(1 << 20);
+    let _s1 = ShmemConf::new()
+        .size(4096)
+        .fixed_address(addr)
+        .anonymous()
+        .create()
+        .unwrap();
+
+    match ShmemConf::new().size(4096).fixed_address(addr).create() {
+        Err(ShmemError::AddressInUse(a)) => assert_eq!(a, addr),
+        Err(e) => panic!("Unexpected error {}", e),
+        Ok(_) => panic!("Replaced an existing mapping"),
+    }
+
+    // Without a header nothing is recorded and the layout is unchanged
+    let s3 = ShmemConf::new()
+        .size(4096)
+        .fixed_address(free_address(1 << 20))
+        .create()
+        .unwrap();
+    assert!(s3.header().is_none());
+    let s4 = ShmemConf::new().id(s3.get_os_id()).open().unwrap();
+    assert_eq!(s4.len(), 4096);
+    assert_ne!(s4.as_ptr(), s3.as_ptr());
+
+    // Hints fall back to another address
+    let s2 = ShmemConf::new()
+        .size(4096)
+        .address_hint(addr)
+        .create()
+        .unwrap();
+    assert_ne!(s2.as_ptr() as usize, addr);
+}
@@ -0,0 +1,187 @@
+use std::fs;
+
+use shared_memory::{ShmemConf, ShmemError};
+
+#[test]
+fn structured_flink() {
+    let flink = "flink_structured";
+    let _ = fs::remove_file(flink);
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/flink_structured")
+        .flink(flink)
+        .header(1, 1)
+        .create()
+        .unwrap();
+
+    let content = fs::read_to_string(flink).unwrap();
+    assert!(content.starts_with("shmem-flink 1\n"));
+    assert!(content.contains("os_id=/flink_structured\n"));
+    assert!(content.contains("size=4096\n"));
+    assert!(content.contains(&format!("pid={}\n", std::process::id())));
+
+    let s2 = ShmemConf::new().flink(flink).header(1, 1).open().unwrap();
+    assert_eq!(s2.get_os_id(), s1.get_os_id());
+
+    // Both the flink and the os_id must agree
+    let s3 = ShmemConf::new()
+        .id("/flink_structured")
+        .flink(flink)
+        .header(1, 1)
+        .open()
+        .unwrap();
+    assert_eq!(s3.get_os_id(), s1.get_os_id());
+    let res = ShmemConf::new()
+        .id("/flink_other")
+        .flink(flink)
+        .header(1, 1)
+        .open();
+    assert!(matches!(res, Err(ShmemError::FlinkInvalidOsId)));
+}
+
+#[test]
+fn recreated_mapping() {
+    let flink = "flink_recreated";
+    let _ = fs::remove_file(flink);
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/flink_recreated")
+        .flink(flink)
+        .header(1, 1)
+        .create()
+        .unwrap();
+    let content = fs::read_to_string(flink).unwrap();
+    drop(s1);
+
+    // Another mapping takes the os_id while the old flink is still around
+    let _s2 = ShmemConf::new()
+        .size(4096)
+        .id("/flink_recreated")
+        .header(1, 1)
+        .create()
+        .unwrap();
+    fs::write(flink, content).unwrap();
+    let res = ShmemConf::new().flink(flink).header(1, 1).open();
+    assert!(matches!(res, Err(ShmemError::FlinkStale)));
+    fs::remove_file(flink).unwrap();
+}
+
+#[test]
+fn flink_mismatch() {
+    let flink = "flink_mismatch";
+    let _ = fs::remove_file(flink);
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/flink_mismatch")
+        .flink(flink)
+        .create()
+        .unwrap();
+    let content = fs::read_to_string(flink).unwrap();
+
+    // The flink does not pick the backend of the opener
+    let backend = content.lines().find(|l| l.starts_with("backend=")).unwrap();
+    fs::write(flink, content.replace(backend, "backend=file")).unwrap();
+    let res = ShmemConf::new().flink(flink).open();
+    assert!(matches!(res, Err(ShmemError::FlinkBackendMismatch(b)) if b == "file"));
+
+    fs::write(flink, &content).unwrap();
+
+    // Resizing from a handle that is not the owner keeps the flink usable
+    #[cfg(unix)]
+    {
+        let mut s2 = ShmemConf::new().flink(flink).open().unwrap();
+        s2.resize(8192).unwrap();
+        let s3 = ShmemConf::new().flink(flink).open().unwrap();
+        assert_eq!(s3.len(), 8192);
+    }
+
+    // Mappings without a header that were recreated under the same os_id are detected
+    drop(s1);
+    #[cfg(unix)]
+    {
+        let _s4 = ShmemConf::new()
+            .size(4096)
+            .id("/flink_mismatch")
+            .create()
+            .unwrap();
+        fs::write(flink, content).unwrap();
+        let res = ShmemConf::new().flink(flink).open();
+        assert!(matches!(res, Err(ShmemError::FlinkStale)));
+        fs::remove_file(flink).unwrap();
+    }
+}
+
+#[test]
+fn legacy_flink() {
+    let flink = "flink_legacy";
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/flink_legacy")
+        .create()
+        .unwrap();
+    fs::write(flink, "/flink_legacy").unwrap();
+    let s2 = ShmemConf::new().flink(flink).open().unwrap();
+    assert_eq!(s2.get_os_id(), s1.get_os_id());
+
+    fs::write(flink, "shmem-flink 9\nos_id=/flink_legacy\n").unwrap();
+    let res = ShmemConf::new().flink(flink).open();
+    assert!(matches!(res, Err(ShmemError::FlinkVersionMismatch(9))));
+    fs::remove_file(flink).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn secure_flink() {
+    let dir = "flink_secure_dir";
+    let _ = fs::remove_dir_all(dir);
+    fs::create_dir(dir).unwrap();
+    let flink = format!("{dir}/flink");
+
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/flink_secure")
+        .flink(&flink)
+        .flink_mode(0o600)
+        .create()
+        .unwrap();
+    // Only the flink is left, the temporary file was renamed
+    assert_eq!(fs::read_dir(dir).unwrap().count(), 1);
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&flink).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+    let res = ShmemConf::new()
+        .size(4096)
+        .id("/flink_secure_other")
+        .flink(&flink)
+        .create();
+    assert!(matches!(res, Err(ShmemError::LinkExists)));
+
+    let uid = unsafe { libc::geteuid() };
+    let s2 = ShmemConf::new()
+        .flink(&flink)
+        .flink_owner(uid)
+        .open()
+        .unwrap();
+    assert_eq!(s2.get_os_id(), s1.get_os_id());
+    let res = ShmemConf::new().flink(&flink).flink_owner(uid + 1).open();
+    assert!(matches!(res, Err(ShmemError::FlinkOwnerMismatch(found)) if found == uid));
+
+    // Symbolic links are not followed
+    let link = format!("{dir}/symlink");
+    std::os::unix::fs::symlink("flink", &link).unwrap();
+    let res = ShmemConf::new().flink(&link).open();
+    assert!(matches!(res, Err(ShmemError::LinkOpenFailed(_))));
+
+    // Opening a fifo would block until someone writes to it
+    let fifo = format!("{dir}/fifo");
+    let c_fifo = std::ffi::CString::new(fifo.as_str()).unwrap();
+    assert_eq!(unsafe { libc::mkfifo(c_fifo.as_ptr(), 0o600) }, 0);
+    let res = ShmemConf::new().flink(&fifo).open();
+    assert!(matches!(res, Err(ShmemError::LinkOpenFailed(_))));
+
+    drop(s2);
+    drop(s1);
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,66 @@
+#![cfg(unix)]
+
+use shared_memory::{Shmem, ShmemConf};
+
+/// Returns the signal that killed a child process touching `addr`
+fn touch_in_child(addr: *mut u8) -> i32 {
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork() failed"),
+        0 => {
+            unsafe { addr.write_volatile(0x42) };
+            unsafe { libc::_exit(0) };
+        }
+        pid => {
+            let mut status = 0;
+            unsafe { libc::waitpid(pid, &mut status, 0) };
+            if libc::WIFSIGNALED(status) {
+                libc::WTERMSIG(status)
+            } else {
+                0
+            }
+        }
+    }
+}
+
+fn check_guards(shmem: &Shmem) {
+    let end = shmem.len().next_multiple_of(shmem.page_size());
+    assert_eq!(
+        touch_in_child(unsafe { shmem.as_ptr().sub(1) }),
+        libc::SIGSEGV
+    );
+    assert_eq!(
+        touch_in_child(unsafe { shmem.as_ptr().add(end) }),
+        libc::SIGSEGV
+    );
+    assert_eq!(touch_in_child(unsafe { shmem.as_ptr().add(end - 1) }), 0);
+}
+
+#[test]
+fn guard_pages() {
+    let mut s1 = ShmemConf::new().size(8192).guard_pages().create().unwrap();
+    assert_eq!(s1.len(), 8192);
+    check_guards(&s1);
+
+    let s2 = ShmemConf::new()
+        .id(s1.get_os_id())
+        .guard_pages()
+        .open()
+        .unwrap();
+    check_guards(&s2);
+
+    // The guards follow the mapping when it moves
+    s1.resize(64 * 4096).unwrap();
+    assert_eq!(s1.len(), 64 * 4096);
+    check_guards(&s1);
+}
+
+#[test]
+fn guard_pages_anonymous() {
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .anonymous()
+        .guard_pages()
+        .create()
+        .unwrap();
+    check_guards(&s1);
+}
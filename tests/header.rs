@@ -0,0 +1,103 @@
+use shared_memory::{ShmemConf, ShmemError};
+
+#[test]
+fn header() {
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/header")
+        .header(0xC0FFEE, 3)
+        .create()
+        .unwrap();
+    assert_eq!(s1.len(), 4096);
+    assert!(!s1.is_initialized());
+    unsafe { s1.as_ptr().write_volatile(0x42) };
+    s1.set_initialized();
+
+    let header = s1.header().unwrap();
+    assert_eq!(header.schema_id, 0xC0FFEE);
+    assert_eq!(header.schema_version, 3);
+    assert_eq!(header.creator_pid, std::process::id());
+    assert_eq!(header.payload_len, 4096);
+    assert!(header.created.elapsed().unwrap().as_secs() < 60);
+
+    let s2 = ShmemConf::new()
+        .id(s1.get_os_id())
+        .header(0xC0FFEE, 3)
+        .open()
+        .unwrap();
+    assert!(s2.is_initialized());
+    assert_eq!(s2.len(), s1.len());
+    assert_eq!(unsafe { s2.as_ptr().read_volatile() }, 0x42);
+    assert_eq!(s2.header(), s1.header());
+
+    // Without a header, the raw bytes are visible
+    let s3 = ShmemConf::new().id(s1.get_os_id()).open().unwrap();
+    assert!(s3.header().is_none());
+    let header_len = s3.len() - s1.len();
+    assert!(header_len > 0);
+    assert_eq!(unsafe { s3.as_ptr().add(header_len).read_volatile() }, 0x42);
+}
+
+#[test]
+fn header_mismatch() {
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/header_mismatch")
+        .header(0xC0FFEE, 3)
+        .create()
+        .unwrap();
+    match ShmemConf::new()
+        .id(s1.get_os_id())
+        .header(0xC0FFEE, 4)
+        .open()
+    {
+        Err(ShmemError::SchemaMismatch { id, version }) => assert_eq!((id, version), (0xC0FFEE, 3)),
+        Err(e) => panic!("Unexpected error {}", e),
+        Ok(_) => panic!("Opened mapping with another schema version"),
+    }
+
+    let raw = ShmemConf::new()
+        .size(4096)
+        .id("/header_mismatch_raw")
+        .create()
+        .unwrap();
+    assert!(matches!(
+        ShmemConf::new()
+            .id(raw.get_os_id())
+            .header(0xC0FFEE, 3)
+            .open(),
+        Err(ShmemError::HeaderInvalid)
+    ));
+}
+
+#[cfg(unix)]
+#[test]
+fn header_layouts() {
+    use shared_memory::CleanupPolicy;
+
+    // Ring buffers wrap around over the header
+    assert!(matches!(
+        ShmemConf::new()
+            .size(4096)
+            .ring_buffer()
+            .cleanup(CleanupPolicy::RefCount)
+            .create(),
+        Err(ShmemError::HeaderNotSupported)
+    ));
+
+    // Windows do not start with the header
+    let s1 = ShmemConf::new()
+        .size(8192)
+        .id("/header_layouts")
+        .header(0xC0FFEE, 3)
+        .create()
+        .unwrap();
+    assert!(matches!(
+        ShmemConf::new()
+            .id(s1.get_os_id())
+            .header(0xC0FFEE, 3)
+            .window(4096, 4096)
+            .open(),
+        Err(ShmemError::HeaderNotSupported)
+    ));
+}
@@ -0,0 +1,37 @@
+#![cfg(target_os = "linux")]
+
+use shared_memory::{HugePageSize, ShmemConf, ShmemError};
+
+#[test]
+fn huge_pages_memfd() {
+    let page = HugePageSize::Size2M.bytes();
+
+    match ShmemConf::new()
+        .size(4096)
+        .memfd()
+        .huge_pages(HugePageSize::Size2M)
+        .create()
+    {
+        Ok(s) => {
+            assert_eq!(s.page_size(), page);
+            assert_eq!(s.len(), page);
+        }
+        // The system has no huge pages reserved
+        Err(ShmemError::HugePagesUnavailable(_)) => {}
+        Err(e) => panic!("unexpected error : {}", e),
+    }
+}
+
+#[test]
+fn huge_pages_shm_unsupported() {
+    assert!(matches!(
+        ShmemConf::new()
+            .size(4096)
+            .huge_pages(HugePageSize::Size2M)
+            .create(),
+        Err(ShmemError::HugePagesUnavailable(_))
+    ));
+
+    let s = ShmemConf::new().size(4096).create().unwrap();
+    assert!(s.page_size() < HugePageSize::Size2M.bytes());
+}
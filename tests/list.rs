@@ -0,0 +1,57 @@
+#![cfg(target_os = "linux")]
+
+use shared_memory::{list_segments, ShmemConf};
+
+#[test]
+fn list() {
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/list_plain")
+        .mode(0o640)
+        .create()
+        .unwrap();
+    let s2 = ShmemConf::new()
+        .size(4096)
+        .id("/list_header")
+        .header(7, 2)
+        .create()
+        .unwrap();
+
+    let segments = list_segments("/list_").unwrap();
+    assert_eq!(segments.len(), 2);
+
+    let header = &segments[0];
+    assert_eq!(header.os_id, s2.get_os_id());
+    assert_eq!(header.header, s2.header());
+    assert!(header.size > s2.len());
+
+    let plain = &segments[1];
+    assert_eq!(plain.os_id, s1.get_os_id());
+    assert_eq!(plain.size, 4096);
+    assert_eq!(plain.uid, unsafe { libc::getuid() });
+    assert_eq!(plain.mode, 0o640);
+    assert!(plain.modified.elapsed().unwrap().as_secs() < 60);
+    assert!(plain.header.is_none());
+
+    assert!(list_segments("/list_nothing").unwrap().is_empty());
+}
+
+#[test]
+fn list_skips_non_objects() {
+    let _s1 = ShmemConf::new()
+        .size(4096)
+        .id("/list_skip_plain")
+        .create()
+        .unwrap();
+    let _ = std::fs::remove_file("/dev/shm/list_skip_link");
+    let _ = std::fs::remove_dir("/dev/shm/list_skip_dir");
+    std::os::unix::fs::symlink("list_skip_plain", "/dev/shm/list_skip_link").unwrap();
+    std::fs::create_dir("/dev/shm/list_skip_dir").unwrap();
+
+    let segments = list_segments("/list_skip_");
+    std::fs::remove_file("/dev/shm/list_skip_link").unwrap();
+    std::fs::remove_dir("/dev/shm/list_skip_dir").unwrap();
+    let segments = segments.unwrap();
+    assert_eq!(segments.len(), 1);
+    assert_eq!(segments[0].os_id, "/list_skip_plain");
+}
@@ -0,0 +1,67 @@
+#![cfg(unix)]
+
+use shared_memory::{ShmemConf, ShmemError};
+
+#[test]
+fn map_options() {
+    let s1 = ShmemConf::new().size(4096).create().unwrap();
+    assert_eq!(s1.map_options(), Default::default());
+
+    let conf = ShmemConf::new().id(s1.get_os_id()).lock();
+    #[cfg(target_os = "linux")]
+    let conf = conf.populate().no_reserve();
+    let s2 = conf.open().unwrap();
+
+    let options = s2.map_options();
+    assert!(options.locked);
+    #[cfg(target_os = "linux")]
+    assert!(options.populated);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn no_reserve() {
+    let s1 = ShmemConf::new().size(4096).no_reserve().create().unwrap();
+    assert!(!s1.map_options().no_reserve);
+
+    let overcommit = std::fs::read_to_string("/proc/sys/vm/overcommit_memory").unwrap();
+    let s2 = ShmemConf::new()
+        .size(4096)
+        .anonymous()
+        .no_reserve()
+        .create()
+        .unwrap();
+    assert_eq!(s2.map_options().no_reserve, overcommit.trim() != "2");
+}
+
+/// Returns whether locking fails with the expected error under a lowered `RLIMIT_MEMLOCK`
+fn lock_limit_exceeded() -> bool {
+    let len = 16 << 20;
+    unsafe {
+        let mut limit: libc::rlimit = std::mem::zeroed();
+        libc::getrlimit(libc::RLIMIT_MEMLOCK, &mut limit);
+        limit.rlim_cur = 64 << 10;
+        libc::setrlimit(libc::RLIMIT_MEMLOCK, &limit);
+    }
+
+    match ShmemConf::new().size(len).anonymous().lock().create() {
+        Err(ShmemError::MemlockLimitExceeded { len: l, limit }) => l == len && limit == 64 << 10,
+        // Privileged processes are not subject to the limit
+        Ok(s) => (unsafe { libc::geteuid() } == 0) && s.map_options().locked,
+        Err(_) => false,
+    }
+}
+
+#[test]
+fn lock_limit() {
+    // The limit applies to the whole process, lower it in a child to leave the other tests alone
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork() failed"),
+        0 => unsafe { libc::_exit(if lock_limit_exceeded() { 0 } else { 1 }) },
+        pid => {
+            let mut status = 0;
+            unsafe { libc::waitpid(pid, &mut status, 0) };
+            assert!(libc::WIFEXITED(status) && libc::WEXITSTATUS(status) == 0);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+#![cfg(target_os = "linux")]
+
+use shared_memory::ShmemConf;
+
+#[test]
+fn memfd_create_and_share() {
+    let s1 = ShmemConf::new().size(4096).memfd().create().unwrap();
+    assert!(s1.is_owner());
+    assert!(s1.len() >= 4096);
+
+    // Segment has no name in the shm namespace
+    assert!(ShmemConf::new().id(s1.get_os_id()).open().is_err());
+
+    let s2 = ShmemConf::new().open_fd(s1.fd().unwrap()).unwrap();
+    assert!(!s2.is_owner());
+    assert_eq!(s2.len(), s1.len());
+    assert_ne!(s1.as_ptr(), s2.as_ptr());
+
+    unsafe {
+        s1.as_ptr().write_volatile(0x42);
+        assert_eq!(s2.as_ptr().read_volatile(), 0x42);
+    }
+
+    // The object stays alive as long as someone maps it
+    drop(s1);
+    unsafe { assert_eq!(s2.as_ptr().read_volatile(), 0x42) };
+}
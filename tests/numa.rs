@@ -0,0 +1,27 @@
+#![cfg(target_os = "linux")]
+
+use shared_memory::{NumaPolicy, ShmemConf};
+
+#[test]
+fn numa_policy() {
+    let s1 = ShmemConf::new()
+        .size(4 * 4096)
+        .numa_policy(NumaPolicy::Bind(vec![0]))
+        .create()
+        .unwrap();
+    assert_eq!(s1.numa_policy().unwrap(), NumaPolicy::Bind(vec![0]));
+
+    // Nothing was touched yet
+    assert!(s1.numa_nodes().unwrap().iter().all(|n| n.is_none()));
+    unsafe { std::ptr::write_bytes(s1.as_ptr(), 0x42, s1.len()) };
+    assert!(s1.numa_nodes().unwrap().iter().all(|n| *n == Some(0)));
+
+    // The policy belongs to the shared memory, not to our mapping
+    let s2 = ShmemConf::new().id(s1.get_os_id()).open().unwrap();
+    assert_eq!(s2.numa_policy().unwrap(), NumaPolicy::Bind(vec![0]));
+
+    s2.set_numa_policy(&NumaPolicy::Preferred(0)).unwrap();
+    assert_eq!(s1.numa_policy().unwrap(), NumaPolicy::Preferred(0));
+    s1.set_numa_policy(&NumaPolicy::Default).unwrap();
+    assert_eq!(s2.numa_policy().unwrap(), NumaPolicy::Default);
+}
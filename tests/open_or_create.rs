@@ -0,0 +1,66 @@
+use std::path::Path;
+use std::sync::{Arc, Barrier};
+use std::thread;
+
+use shared_memory::{ShmemConf, ShmemOrigin};
+
+#[test]
+fn open_or_create_race() {
+    let flink = "open_or_create_race";
+    let _ = std::fs::remove_file(flink);
+
+    let barrier = Arc::new(Barrier::new(8));
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                let (shmem, origin) = ShmemConf::new()
+                    .size(4096)
+                    .flink(flink)
+                    .open_or_create()
+                    .unwrap();
+                // Keep every handle alive until everyone got one
+                barrier.wait();
+                (shmem.get_os_id().to_string(), origin)
+            })
+        })
+        .collect();
+
+    let results: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    assert_eq!(
+        results
+            .iter()
+            .filter(|(_, o)| *o == ShmemOrigin::Created)
+            .count(),
+        1
+    );
+    assert!(results.iter().all(|(id, _)| *id == results[0].0));
+    assert!(!Path::new(flink).is_file());
+}
+
+#[test]
+fn open_or_create_force_replace() {
+    let os_id = "/open_or_create_force_replace";
+    let mut stale = ShmemConf::new().size(4096).id(os_id).create().unwrap();
+    stale.set_owner(false);
+    drop(stale);
+
+    let (s, origin) = ShmemConf::new()
+        .size(4096)
+        .id(os_id)
+        .open_or_create()
+        .unwrap();
+    assert_eq!(origin, ShmemOrigin::Opened);
+    drop(s);
+
+    let (s, origin) = ShmemConf::new()
+        .size(8192)
+        .id(os_id)
+        .force_replace()
+        .open_or_create()
+        .unwrap();
+    assert_eq!(origin, ShmemOrigin::Created);
+    assert!(s.is_owner());
+    assert!(s.len() >= 8192);
+}
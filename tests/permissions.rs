@@ -0,0 +1,81 @@
+#![cfg(unix)]
+
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::Path;
+
+use shared_memory::ShmemConf;
+
+#[test]
+fn mode_and_group() {
+    let flink = Path::new("permissions_flink");
+    let gid = unsafe { libc::getgid() };
+
+    let s = ShmemConf::new()
+        .size(4096)
+        .flink(flink)
+        .mode(0o640)
+        .flink_mode(0o604)
+        .group(gid)
+        .ignore_umask()
+        .create()
+        .unwrap();
+
+    let meta = std::fs::metadata(flink).unwrap();
+    assert_eq!(meta.permissions().mode() & 0o777, 0o604);
+    assert_eq!(meta.gid(), gid);
+
+    #[cfg(target_os = "linux")]
+    {
+        let meta = std::fs::metadata(format!("/dev/shm{}", s.get_os_id())).unwrap();
+        assert_eq!(meta.permissions().mode() & 0o777, 0o640);
+        assert_eq!(meta.gid(), gid);
+    }
+}
+
+#[test]
+fn mode_respects_umask() {
+    let path = Path::new("permissions_umask");
+    let reference = Path::new("permissions_umask_reference");
+    let _ = std::fs::remove_file(path);
+    let _ = std::fs::remove_file(reference);
+
+    let mut s = ShmemConf::new()
+        .backing_file(path)
+        .size(4096)
+        .mode(0o666)
+        .create()
+        .unwrap();
+    s.set_owner(true);
+
+    // Reading the umask means changing it, compare with a file created the usual way instead
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o666)
+        .open(reference)
+        .unwrap();
+    let expected = std::fs::metadata(reference).unwrap().permissions().mode();
+    std::fs::remove_file(reference).unwrap();
+    let meta = std::fs::metadata(path).unwrap();
+    assert_eq!(meta.permissions().mode() & 0o777, expected & 0o777);
+}
+
+#[test]
+fn existing_file_keeps_mode() {
+    let path = Path::new("permissions_existing");
+    let _ = std::fs::remove_file(path);
+    std::fs::write(path, [0u8; 4096]).unwrap();
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600)).unwrap();
+
+    let mut s = ShmemConf::new()
+        .backing_file(path)
+        .size(4096)
+        .mode(0o666)
+        .ignore_umask()
+        .create()
+        .unwrap();
+    s.set_owner(true);
+
+    let meta = std::fs::metadata(path).unwrap();
+    assert_eq!(meta.permissions().mode() & 0o777, 0o600);
+}
@@ -0,0 +1,19 @@
+use shared_memory::ShmemConf;
+
+#[test]
+fn open_read_only() {
+    let s1 = ShmemConf::new().size(4096).create().unwrap();
+    unsafe { s1.as_ptr().write_volatile(0x42) };
+
+    let s2 = ShmemConf::new()
+        .id(s1.get_os_id())
+        .open_read_only()
+        .unwrap();
+
+    assert_eq!(s2.get_os_id(), s1.get_os_id());
+    assert_eq!(s2.len(), s1.len());
+    unsafe {
+        assert_eq!(s2.as_ptr().read_volatile(), 0x42);
+        assert_eq!(s2.as_slice()[0], 0x42);
+    }
+}
@@ -0,0 +1,76 @@
+#![cfg(target_os = "linux")]
+
+use std::path::Path;
+
+use shared_memory::{reclaim_stale, ShmemConf};
+
+#[test]
+fn default_ids_are_unique() {
+    let s1 = ShmemConf::new().size(4096).create().unwrap();
+    let s2 = ShmemConf::new().size(4096).create().unwrap();
+    assert_ne!(s1.get_os_id(), s2.get_os_id());
+}
+
+#[test]
+fn reclaim() {
+    let _ = std::fs::remove_file("reclaim_flink_dead");
+
+    let alive = ShmemConf::new()
+        .size(4096)
+        .id("/reclaim_alive")
+        .create()
+        .unwrap();
+
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork() failed"),
+        0 => {
+            // Crash while holding the mappings
+            let dead = ShmemConf::new()
+                .size(4096)
+                .id("/reclaim_dead")
+                .header(1, 1)
+                .create()
+                .unwrap();
+            let flinked = ShmemConf::new()
+                .size(4096)
+                .id("/reclaim_flinked")
+                .header(1, 1)
+                .flink("reclaim_flink_dead")
+                .create()
+                .unwrap();
+            // Nothing tells who created it
+            let unknown = ShmemConf::new()
+                .size(4096)
+                .id("/reclaim_unknown")
+                .create()
+                .unwrap();
+            std::mem::forget(dead);
+            std::mem::forget(flinked);
+            std::mem::forget(unknown);
+            unsafe { libc::_exit(0) };
+        }
+        pid => {
+            let mut status = 0;
+            unsafe { libc::waitpid(pid, &mut status, 0) };
+            assert!(libc::WIFEXITED(status));
+        }
+    }
+    assert!(Path::new("reclaim_flink_dead").is_file());
+
+    let reclaimed = reclaim_stale("./reclaim_flink_").unwrap();
+    assert_eq!(reclaimed.segments, ["/reclaim_flinked"]);
+    assert_eq!(
+        reclaimed.flinks,
+        [Path::new("./reclaim_flink_dead").to_path_buf()]
+    );
+    assert!(!Path::new("reclaim_flink_dead").exists());
+
+    let reclaimed = reclaim_stale("/reclaim_").unwrap();
+    assert_eq!(reclaimed.segments, ["/reclaim_dead"]);
+    assert!(reclaimed.flinks.is_empty());
+
+    assert!(ShmemConf::new().id("/reclaim_dead").open().is_err());
+    assert!(ShmemConf::new().id(alive.get_os_id()).open().is_ok());
+    let mut unknown = ShmemConf::new().id("/reclaim_unknown").open().unwrap();
+    unknown.set_owner(true);
+}
@@ -0,0 +1,69 @@
+#![cfg(unix)]
+
+use shared_memory::{CleanupPolicy, ShmemConf, ShmemError};
+
+fn open(os_id: &str) -> Result<shared_memory::Shmem, ShmemError> {
+    ShmemConf::new()
+        .id(os_id)
+        .cleanup(CleanupPolicy::RefCount)
+        .open()
+}
+
+#[test]
+fn last_detach_unlinks() {
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/refcount")
+        .cleanup(CleanupPolicy::RefCount)
+        .create()
+        .unwrap();
+    assert_eq!(s1.ref_count(), Some(1));
+
+    let s2 = open("/refcount").unwrap();
+    assert_eq!(s1.ref_count(), Some(2));
+
+    // The creator leaving does not lock out later joiners
+    drop(s1);
+    assert_eq!(s2.ref_count(), Some(1));
+    let s3 = open("/refcount").unwrap();
+    assert_eq!(s3.ref_count(), Some(2));
+
+    drop(s2);
+    drop(s3);
+    assert!(matches!(
+        open("/refcount"),
+        Err(ShmemError::MapOpenFailed(_))
+    ));
+}
+
+#[test]
+fn crashed_process_is_ignored() {
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/refcount_crash")
+        .flink("refcount_crash_flink")
+        .force_create_flink()
+        .cleanup(CleanupPolicy::RefCount)
+        .create()
+        .unwrap();
+
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork() failed"),
+        0 => {
+            // Exits without detaching
+            let s2 = open("/refcount_crash").unwrap();
+            std::mem::forget(s2);
+            unsafe { libc::_exit(0) };
+        }
+        pid => {
+            let mut status = 0;
+            unsafe { libc::waitpid(pid, &mut status, 0) };
+            assert!(libc::WIFEXITED(status));
+        }
+    }
+
+    assert_eq!(s1.ref_count(), Some(2));
+    drop(s1);
+    assert!(!std::path::Path::new("refcount_crash_flink").exists());
+    assert!(open("/refcount_crash").is_err());
+}
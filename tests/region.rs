@@ -0,0 +1,89 @@
+#![cfg(unix)]
+
+use shared_memory::{Advice, Protection, ShmemConf, ShmemError};
+
+#[test]
+fn region_errors() {
+    let shmem = ShmemConf::new().size(4 * 4096).create().unwrap();
+    let page = shmem.page_size();
+
+    assert!(matches!(
+        shmem.protect(page, shmem.len(), Protection::ReadOnly),
+        Err(ShmemError::RangeOutOfBounds { .. })
+    ));
+    assert!(matches!(
+        shmem.advise(1, page, Advice::WillNeed),
+        Err(ShmemError::RangeNotAligned { offset: 1, .. })
+    ));
+    assert!(matches!(
+        shmem.lock_range(usize::MAX, 2),
+        Err(ShmemError::RangeOutOfBounds { .. })
+    ));
+}
+
+#[test]
+fn region_header() {
+    let shmem = ShmemConf::new()
+        .size(4 * 4096)
+        .header(1, 1)
+        .create()
+        .unwrap();
+    let page = shmem.page_size();
+    let first_page = page - (shmem.as_ptr() as usize % page);
+
+    // Errors report the offset relative to the payload
+    assert!(matches!(
+        shmem.protect(0, page, Protection::ReadOnly),
+        Err(ShmemError::RangeNotAligned { offset: 0, .. })
+    ));
+    assert!(matches!(
+        shmem.lock_range(usize::MAX, 2),
+        Err(ShmemError::RangeOutOfBounds {
+            offset: usize::MAX,
+            ..
+        })
+    ));
+
+    // The first page boundary of the payload is right after the header's page
+    shmem.advise(first_page, page, Advice::WillNeed).unwrap();
+    shmem
+        .protect(first_page, page, Protection::ReadOnly)
+        .unwrap();
+    shmem
+        .protect(first_page, page, Protection::ReadWrite)
+        .unwrap();
+}
+
+#[test]
+fn region_control() {
+    let shmem = ShmemConf::new().size(4 * 4096).create().unwrap();
+    let page = shmem.page_size();
+
+    shmem.advise(0, shmem.len(), Advice::Sequential).unwrap();
+    shmem.advise(0, shmem.len(), Advice::WillNeed).unwrap();
+    unsafe { shmem.as_ptr().write_volatile(0x42) };
+    // The content is still there after dropping our pages
+    shmem.advise(0, page, Advice::DontNeed).unwrap();
+    assert_eq!(unsafe { shmem.as_ptr().read_volatile() }, 0x42);
+
+    shmem.lock_range(page, page).unwrap();
+    shmem.unlock_range(page, page).unwrap();
+
+    shmem.protect(page, page, Protection::ReadOnly).unwrap();
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork() failed"),
+        0 => {
+            unsafe { shmem.as_ptr().add(page).write_volatile(0x42) };
+            unsafe { libc::_exit(0) };
+        }
+        pid => {
+            let mut status = 0;
+            unsafe { libc::waitpid(pid, &mut status, 0) };
+            assert!(libc::WIFSIGNALED(status));
+            assert_eq!(libc::WTERMSIG(status), libc::SIGSEGV);
+        }
+    }
+
+    shmem.protect(page, page, Protection::ReadWrite).unwrap();
+    unsafe { shmem.as_ptr().add(page).write_volatile(0x42) };
+}
@@ -0,0 +1,49 @@
+use std::path::Path;
+
+use shared_memory::ShmemConf;
+
+fn publish(flink: &str, value: u8) -> shared_memory::Shmem {
+    let shmem = ShmemConf::new()
+        .size(4096)
+        .id("/republish")
+        .flink(flink)
+        .republish()
+        .unwrap();
+    unsafe { shmem.as_ptr().write_volatile(value) };
+    shmem
+}
+
+#[test]
+fn republish() {
+    let flink = "republish_flink";
+    let _ = std::fs::remove_file(flink);
+
+    let v1 = publish(flink, 1);
+    assert!(v1.get_os_id().starts_with("/republish_"));
+    let r1 = ShmemConf::new().flink(flink).open_read_only().unwrap();
+    assert_eq!(r1.get_os_id(), v1.get_os_id());
+    assert!(!r1.is_stale());
+
+    let v2 = publish(flink, 2);
+    assert_ne!(v2.get_os_id(), v1.get_os_id());
+    assert!(r1.is_stale());
+    assert!(v1.is_stale());
+    assert!(!v2.is_stale());
+
+    let r2 = r1.reopen().unwrap();
+    assert_eq!(r2.get_os_id(), v2.get_os_id());
+    assert_eq!(unsafe { r2.as_ptr().read_volatile() }, 2);
+    assert!(!r2.is_stale());
+
+    // The previous snapshot goes away without taking the flink with it
+    drop(v1);
+    assert!(Path::new(flink).is_file());
+    assert_eq!(unsafe { r1.as_ptr().read_volatile() }, 1);
+    let res = ShmemConf::new().id(r1.get_os_id()).open();
+    assert!(res.is_err());
+    drop(r1);
+
+    drop(v2);
+    assert!(!Path::new(flink).exists());
+    assert!(r2.is_stale());
+}
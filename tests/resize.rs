@@ -0,0 +1,29 @@
+#![cfg(unix)]
+
+use shared_memory::ShmemConf;
+
+#[test]
+fn resize_and_refresh() {
+    let mut s1 = ShmemConf::new().size(4096).create().unwrap();
+    let mut s2 = ShmemConf::new().id(s1.get_os_id()).open().unwrap();
+
+    // Nothing changed yet
+    assert!(!s2.refresh().unwrap());
+
+    // Grow the mapping and write at the end of it
+    s1.resize(4 * 4096).unwrap();
+    assert_eq!(s1.len(), 4 * 4096);
+    unsafe { s1.as_ptr().add(s1.len() - 1).write_volatile(0x42) };
+
+    // Peer notices the new size
+    assert!(s2.refresh().unwrap());
+    assert_eq!(s2.len(), 4 * 4096);
+    unsafe { assert_eq!(s2.as_ptr().add(s2.len() - 1).read_volatile(), 0x42) };
+
+    // Shrink from the peer side
+    s2.resize(2048).unwrap();
+    assert!(s1.refresh().unwrap());
+    assert_eq!(s1.len(), 2048);
+
+    assert!(s1.resize(0).is_err());
+}
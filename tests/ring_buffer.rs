@@ -0,0 +1,56 @@
+#![cfg(unix)]
+
+use shared_memory::{ShmemConf, ShmemError};
+
+#[test]
+fn ring_buffer() {
+    let s1 = ShmemConf::new().size(100).ring_buffer().create().unwrap();
+    let len = s1.len();
+    assert_eq!(len, s1.page_size());
+
+    // Writing past the end wraps around to the start
+    let data = unsafe { std::slice::from_raw_parts_mut(s1.as_ptr(), 2 * len) };
+    data[len - 2..len + 2].copy_from_slice(&[1, 2, 3, 4]);
+    assert_eq!(&data[..2], &[3, 4]);
+    assert_eq!(&data[2 * len - 2..], &[1, 2]);
+
+    let s2 = ShmemConf::new()
+        .id(s1.get_os_id())
+        .ring_buffer()
+        .open()
+        .unwrap();
+    assert_eq!(s2.len(), len);
+    unsafe { s2.as_ptr().add(len + 10).write_volatile(0x42) };
+    assert_eq!(data[10], 0x42);
+    assert_eq!(data[len + 10], 0x42);
+}
+
+#[test]
+fn ring_buffer_alignment() {
+    let s1 = ShmemConf::new().size(3 * 4096).create().unwrap();
+
+    assert!(matches!(
+        ShmemConf::new()
+            .id(s1.get_os_id())
+            .window(100, 4096)
+            .ring_buffer()
+            .open(),
+        Err(ShmemError::RangeNotAligned { offset: 100, .. })
+    ));
+
+    let s2 = ShmemConf::new()
+        .id(s1.get_os_id())
+        .window(4096, 4096)
+        .ring_buffer()
+        .open()
+        .unwrap();
+    unsafe { s2.as_ptr().add(4096).write_volatile(0x42) };
+    assert_eq!(unsafe { s1.as_ptr().add(4096).read_volatile() }, 0x42);
+
+    assert!(ShmemConf::new()
+        .size(4096)
+        .anonymous()
+        .ring_buffer()
+        .create()
+        .is_err());
+}
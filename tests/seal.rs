@@ -0,0 +1,124 @@
+#![cfg(unix)]
+
+use shared_memory::{Seals, ShmemConf, ShmemError};
+
+/// Returns whether writing to `addr` from a child process crashes it
+fn write_faults(addr: *mut u8) -> bool {
+    match unsafe { libc::fork() } {
+        -1 => panic!("fork() failed"),
+        0 => {
+            unsafe { addr.write_volatile(0x42) };
+            unsafe { libc::_exit(0) };
+        }
+        pid => {
+            let mut status = 0;
+            unsafe { libc::waitpid(pid, &mut status, 0) };
+            libc::WIFSIGNALED(status)
+        }
+    }
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn seal_memfd() {
+    let mut s1 = ShmemConf::new().size(4096).memfd().create().unwrap();
+    unsafe { s1.as_ptr().write_volatile(0x24) };
+    let ptr = s1.as_ptr();
+
+    // Writable mappings prevent sealing against writes
+    let s2 = ShmemConf::new().open_fd(s1.fd().unwrap()).unwrap();
+    assert!(matches!(
+        s1.seal(Seals::ALL),
+        Err(ShmemError::MapSealFailed(e)) if e == libc::EBUSY as u32
+    ));
+    // The mapping is still writable after sealing failed
+    assert!(!write_faults(s1.as_ptr()));
+    unsafe { s1.as_ptr().write_volatile(0x24) };
+    drop(s2);
+
+    s1.seal(Seals::ALL).unwrap();
+    assert_eq!(s1.seals().unwrap(), Seals::ALL);
+    assert_eq!(s1.as_ptr(), ptr);
+    assert_eq!(unsafe { s1.as_ptr().read_volatile() }, 0x24);
+    assert!(write_faults(s1.as_ptr()));
+    assert!(s1.resize(8192).is_err());
+
+    let s3 = ShmemConf::new()
+        .require_seals(Seals::ALL)
+        .open_fd_read_only(s1.fd().unwrap())
+        .unwrap();
+    assert_eq!(unsafe { s3.as_ptr().read_volatile() }, 0x24);
+    assert!(ShmemConf::new().open_fd(s1.fd().unwrap()).is_err());
+    assert!(ShmemConf::new().open_fd_read_only(s3.fd().unwrap()).is_ok());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn seal_memfd_size() {
+    let mut s1 = ShmemConf::new().size(4096).memfd().create().unwrap();
+    s1.seal(Seals {
+        no_grow: true,
+        ..Default::default()
+    })
+    .unwrap();
+    assert!(!write_faults(s1.as_ptr()));
+    assert!(s1.resize(8192).is_err());
+    s1.resize(2048).unwrap();
+
+    assert!(matches!(
+        ShmemConf::new()
+            .require_seals(Seals::ALL)
+            .open_fd(s1.fd().unwrap()),
+        Err(ShmemError::NotSealed)
+    ));
+}
+
+#[test]
+fn seal_fallback() {
+    let write_protected = Seals {
+        write_protected: true,
+        ..Default::default()
+    };
+    let mut s1 = ShmemConf::new().size(4096).create().unwrap();
+    assert!(matches!(
+        ShmemConf::new()
+            .id(s1.get_os_id())
+            .require_seals(write_protected)
+            .open_read_only(),
+        Err(ShmemError::NotSealed)
+    ));
+
+    // Sizes cannot be sealed without the kernel, the write protection is not applied alone either
+    assert!(s1
+        .seal(Seals {
+            no_grow: true,
+            ..Default::default()
+        })
+        .is_err());
+    assert!(s1.seal(Seals::ALL).is_err());
+    assert!(!write_faults(s1.as_ptr()));
+    assert_eq!(s1.seals().unwrap(), Seals::default());
+
+    s1.seal(Seals {
+        no_write: true,
+        ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(s1.seals().unwrap(), write_protected);
+    assert!(write_faults(s1.as_ptr()));
+
+    // Write permissions are not a kernel seal
+    assert!(matches!(
+        ShmemConf::new()
+            .id(s1.get_os_id())
+            .require_seals(Seals::ALL)
+            .open_read_only(),
+        Err(ShmemError::NotSealed)
+    ));
+    let s2 = ShmemConf::new()
+        .id(s1.get_os_id())
+        .require_seals(write_protected)
+        .open_read_only()
+        .unwrap();
+    assert_eq!(s2.seals().unwrap(), write_protected);
+}
@@ -0,0 +1,49 @@
+#![cfg(unix)]
+
+use shared_memory::ShmemConf;
+
+#[test]
+fn stat() {
+    let s1 = ShmemConf::new()
+        .size(4096)
+        .id("/stat")
+        .mode(0o640)
+        .create()
+        .unwrap();
+    let st = s1.stat().unwrap();
+    assert_eq!(st.uid, unsafe { libc::getuid() });
+    assert_eq!(st.gid, unsafe { libc::getgid() });
+    assert_eq!(st.mode, 0o640);
+    assert_eq!(st.size, 4096);
+    assert!(st.modified.elapsed().unwrap().as_secs() < 60);
+    assert!(!st.unlinked);
+
+    let s2 = ShmemConf::new().id("/stat").open().unwrap();
+    assert!(!s2.stat().unwrap().unlinked);
+
+    // The creator deletes the name, a new mapping takes it over
+    drop(s1);
+    assert!(s2.stat().unwrap().unlinked);
+    let s3 = ShmemConf::new().size(4096).id("/stat").create().unwrap();
+    assert!(s2.stat().unwrap().unlinked);
+    assert!(!s3.stat().unwrap().unlinked);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn stat_memfd() {
+    let s1 = ShmemConf::new().size(4096).memfd().create().unwrap();
+    let st = s1.stat().unwrap();
+    assert_eq!(st.size, 4096);
+    assert!(!st.unlinked);
+
+    // Neither are handles opened from its file descriptor
+    let s2 = ShmemConf::new().open_fd(s1.fd().unwrap()).unwrap();
+    assert!(!s2.stat().unwrap().unlinked);
+}
+
+#[test]
+fn stat_anonymous() {
+    let s = ShmemConf::new().size(4096).anonymous().create().unwrap();
+    assert!(s.stat().is_err());
+}
@@ -0,0 +1,94 @@
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use shared_memory::{RetryPolicy, ShmemConf, ShmemError, Timeout};
+
+/// Creates the mapping after a delay and keeps it until `done` is signaled
+fn create_later(conf: ShmemConf, done: mpsc::Receiver<()>) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(100));
+        let shmem = conf.create().unwrap();
+        unsafe { shmem.as_ptr().write_volatile(0x42) };
+        thread::sleep(Duration::from_millis(100));
+        shmem.set_initialized();
+        done.recv().unwrap();
+    })
+}
+
+#[test]
+fn wait_os_id() {
+    let (tx, rx) = mpsc::channel();
+    let creator = create_later(
+        ShmemConf::new().size(4096).id("/wait_os_id").header(1, 1),
+        rx,
+    );
+
+    let shmem = ShmemConf::new()
+        .id("/wait_os_id")
+        .header(1, 1)
+        .open_wait(Timeout::Val(Duration::from_secs(10)))
+        .unwrap();
+    assert!(shmem.is_initialized());
+    assert_eq!(unsafe { shmem.as_ptr().read_volatile() }, 0x42);
+
+    tx.send(()).unwrap();
+    creator.join().unwrap();
+}
+
+#[test]
+fn wait_flink() {
+    let _ = std::fs::remove_file("wait_flink");
+    let (tx, rx) = mpsc::channel();
+    let creator = create_later(
+        ShmemConf::new()
+            .size(4096)
+            .id("/wait_flink")
+            .flink("wait_flink")
+            .header(1, 1),
+        rx,
+    );
+
+    let shmem = ShmemConf::new()
+        .flink("wait_flink")
+        .header(1, 1)
+        .open_read_only_wait(Timeout::Infinite)
+        .unwrap();
+    assert!(shmem.is_initialized());
+    assert_eq!(unsafe { shmem.as_ptr().read_volatile() }, 0x42);
+
+    tx.send(()).unwrap();
+    creator.join().unwrap();
+}
+
+#[test]
+fn wait_timeout() {
+    let start = Instant::now();
+    let res = ShmemConf::new()
+        .id("/wait_nothing")
+        .open_wait(Timeout::Val(Duration::from_millis(100)));
+    assert!(matches!(res, Err(ShmemError::WaitTimedOut)));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+
+    // Other errors are returned right away
+    let res = ShmemConf::new().open_wait(Timeout::Infinite);
+    assert!(matches!(res, Err(ShmemError::NoLinkOrOsId)));
+}
+
+#[test]
+fn retry_policy() {
+    let flink = "wait_retry_flink";
+    std::fs::write(flink, "/wait_retry_missing").unwrap();
+
+    let start = Instant::now();
+    let res = ShmemConf::new()
+        .flink(flink)
+        .retry(RetryPolicy {
+            attempts: 2,
+            delay: Duration::from_millis(10),
+        })
+        .open();
+    assert!(matches!(res, Err(ShmemError::MapOpenFailed(_))));
+    assert!(start.elapsed() < Duration::from_millis(250));
+    std::fs::remove_file(flink).unwrap();
+}
@@ -0,0 +1,63 @@
+#![cfg(unix)]
+
+use shared_memory::{ShmemConf, ShmemError};
+
+#[test]
+fn open_window() {
+    let s1 = ShmemConf::new().size(4 * 4096).create().unwrap();
+    let data = unsafe { std::slice::from_raw_parts_mut(s1.as_ptr(), s1.len()) };
+    for (i, v) in data.iter_mut().enumerate() {
+        *v = (i % 251) as u8;
+    }
+
+    let s2 = ShmemConf::new()
+        .id(s1.get_os_id())
+        .window(5000, 3000)
+        .open()
+        .unwrap();
+    assert_eq!(s2.len(), 3000);
+    assert_eq!(s2.offset(), 5000);
+    let window = unsafe { s2.as_slice() };
+    assert_eq!(window, &data[5000..8000]);
+
+    unsafe { s2.as_ptr().write_volatile(0xFF) };
+    assert_eq!(data[5000], 0xFF);
+
+    assert!(matches!(
+        ShmemConf::new()
+            .id(s1.get_os_id())
+            .window(4096, 4 * 4096)
+            .open(),
+        Err(ShmemError::RangeOutOfBounds { .. })
+    ));
+
+    // Windows only apply to open()
+    assert!(matches!(
+        ShmemConf::new().size(8192).window(4096, 4096).create(),
+        Err(ShmemError::MapCreateFailed(e)) if e == libc::EINVAL as u32
+    ));
+}
+
+#[test]
+fn map_windows() {
+    let s1 = ShmemConf::new().size(4 * 4096).create().unwrap();
+    let data = unsafe { std::slice::from_raw_parts_mut(s1.as_ptr(), s1.len()) };
+    for (i, v) in data.iter_mut().enumerate() {
+        *v = (i % 251) as u8;
+    }
+
+    let w1 = s1.map_window(100, 10).unwrap();
+    let w2 = s1.map_window(3 * 4096, 4096).unwrap();
+    assert_eq!(unsafe { w1.as_slice() }, &data[100..110]);
+    assert_eq!(unsafe { w2.as_slice() }, &data[3 * 4096..]);
+    assert_eq!((w1.offset(), w1.len()), (100, 10));
+
+    // Windows outlive the handle they were created from
+    let id = s1.get_os_id().to_string();
+    drop(s1);
+    assert_eq!(
+        unsafe { w2.as_ptr().read_volatile() },
+        ((3 * 4096) % 251) as u8
+    );
+    assert!(ShmemConf::new().id(id).open().is_err());
+}